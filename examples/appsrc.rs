@@ -0,0 +1,88 @@
+use gstreamer_iced::*;
+use iced::Length;
+use iced::widget::container;
+use std::time::Duration;
+
+const WIDTH: u32 = 640;
+const HEIGHT: u32 = 360;
+
+fn main() -> iced::Result {
+    iced::application(GProgram::new, GProgram::update, GProgram::view)
+        .title(GProgram::title)
+        .run()
+}
+
+#[derive(Debug)]
+struct GProgram {
+    video: GVideo,
+}
+
+#[derive(Debug, Clone)]
+enum GIcedMessage {
+    StateChanged(gstreamer::State),
+}
+
+/// a moving gradient, generated in rust
+fn generate_frame(index: u64) -> Vec<u8> {
+    let mut data = Vec::with_capacity((WIDTH * HEIGHT * 4) as usize);
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            data.push(((x as u64 + index * 4) % 256) as u8);
+            data.push(((y as u64 + index * 2) % 256) as u8);
+            data.push((index % 256) as u8);
+            data.push(255);
+        }
+    }
+    data
+}
+
+impl GProgram {
+    fn view(&'_ self) -> iced::Element<'_, GIcedMessage> {
+        let video = VideoPlayer::new(&self.video)
+            .on_state_changed(GIcedMessage::StateChanged)
+            .width(Length::Fill);
+
+        container(video)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .center_x(Length::Fill)
+            .center_y(Length::Fill)
+            .into()
+    }
+
+    fn update(&mut self, message: GIcedMessage) -> iced::Task<GIcedMessage> {
+        match message {
+            GIcedMessage::StateChanged(_state) => iced::Task::none(),
+        }
+    }
+
+    fn title(&self) -> String {
+        "Iced Gstreamer".to_string()
+    }
+
+    fn new() -> Self {
+        let video = GVideo::new_appsrc().build().unwrap();
+        let handle = video.as_appsrc().handle();
+        std::thread::spawn(move || {
+            let frame_duration = Duration::from_millis(33);
+            for index in 0.. {
+                let frame = generate_frame(index);
+                if handle
+                    .push_frame(
+                        AppSrcFormat::Rgba,
+                        WIDTH,
+                        HEIGHT,
+                        frame,
+                        frame_duration * index as u32,
+                    )
+                    .is_err()
+                {
+                    break;
+                }
+                std::thread::sleep(frame_duration);
+            }
+        });
+
+        Self { video }
+    }
+}
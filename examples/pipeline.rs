@@ -0,0 +1,54 @@
+use gstreamer_iced::*;
+use iced::Length;
+use iced::widget::container;
+
+fn main() -> iced::Result {
+    iced::application(GProgram::new, GProgram::update, GProgram::view)
+        .title(GProgram::title)
+        .run()
+}
+
+#[derive(Debug)]
+struct GProgram {
+    video: GVideo,
+}
+
+#[derive(Debug, Clone)]
+enum GIcedMessage {
+    StateChanged(gstreamer::State),
+}
+
+impl GProgram {
+    fn view(&'_ self) -> iced::Element<'_, GIcedMessage> {
+        let video = VideoPlayer::new(&self.video)
+            .on_state_changed(GIcedMessage::StateChanged)
+            .width(Length::Fill);
+
+        container(video)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .center_x(Length::Fill)
+            .center_y(Length::Fill)
+            .into()
+    }
+
+    fn update(&mut self, message: GIcedMessage) -> iced::Task<GIcedMessage> {
+        match message {
+            GIcedMessage::StateChanged(_state) => iced::Task::none(),
+        }
+    }
+
+    fn title(&self) -> String {
+        "Iced Gstreamer".to_string()
+    }
+
+    fn new() -> Self {
+        let video = GVideo::new_pipeline(
+            "videotestsrc pattern=ball is-live=true ! video/x-raw,width=1280,height=720",
+        )
+        .build()
+        .unwrap();
+
+        Self { video }
+    }
+}
@@ -54,21 +54,26 @@ struct GProgram {
     video: GVideo,
     fd: Option<Arc<OwnedFd>>,
     state: gstreamer::State,
+    recording: bool,
+    recordings: usize,
 }
 #[derive(Debug, Clone)]
 enum GIcedMessage {
     Ready((u32, Arc<OwnedFd>)),
+    StartRecording,
     StopRecording,
     StateChanged(gstreamer::State),
 }
 
 impl GProgram {
     fn view(&'_ self) -> iced::Element<'_, GIcedMessage> {
-        let btn = button(text("[]")).on_press_maybe(if self.state == PlayingState::Playing {
-            Some(GIcedMessage::StopRecording)
+        let btn = if self.recording {
+            button(text("[]")).on_press(GIcedMessage::StopRecording)
         } else {
-            None
-        });
+            button(text("o")).on_press_maybe(
+                (self.state == PlayingState::Playing).then_some(GIcedMessage::StartRecording),
+            )
+        };
 
         let video = VideoPlayer::new(&self.video)
             .on_state_changed(GIcedMessage::StateChanged)
@@ -87,8 +92,21 @@ impl GProgram {
 
     fn update(&mut self, message: GIcedMessage) -> iced::Task<GIcedMessage> {
         match message {
+            GIcedMessage::StartRecording => {
+                self.recordings += 1;
+                self.video
+                    .as_pw()
+                    .start_recording(
+                        format!("record-{}.mp4", self.recordings),
+                        &Self::recording_options(),
+                    )
+                    .unwrap();
+                self.recording = true;
+                Task::none()
+            }
             GIcedMessage::StopRecording => {
                 self.video.as_pw().stop_recording();
+                self.recording = false;
                 Task::none()
             }
             GIcedMessage::StateChanged(state) => {
@@ -100,14 +118,24 @@ impl GProgram {
                 self.video
                     .open_pipewire(path, fd.as_raw_fd())
                     .save_file("record.mp4")
+                    .recording_options(Self::recording_options())
                     .finish()
                     .unwrap();
+                self.recording = true;
                 self.state = self.video.play_state();
                 Task::none()
             }
         }
     }
 
+    fn recording_options() -> RecordingOptions {
+        RecordingOptions::new()
+            .preset(EncoderPreset::UltraFast)
+            .keyframe_interval(60)
+            // the file can be played even if the app is killed while recording
+            .mp4_mode(Mp4Mode::Fragmented(std::time::Duration::from_secs(1)))
+    }
+
     fn title(&self) -> String {
         "Iced Gstreamer".to_string()
     }
@@ -118,6 +146,8 @@ impl GProgram {
             Self {
                 fd: None,
                 state: video.play_state(),
+                recording: false,
+                recordings: 0,
                 video,
             },
             iced::Task::perform(async { get_path().await.unwrap() }, GIcedMessage::Ready),
@@ -0,0 +1,176 @@
+use gst::prelude::*;
+use gstreamer as gst;
+use iced_futures::Subscription;
+use iced_futures::futures::{StreamExt, future, stream};
+
+use crate::IcedGStreamerError;
+use crate::recording::make_element;
+
+/// The device class of the audio outputs for the device monitor
+const AUDIO_SINK_CLASS: &str = "Audio/Sink";
+
+/// The name of the element which plays the sound in the audio sink of playbin
+pub(crate) const AUDIO_OUTPUT_NAME: &str = "audio_output";
+
+/// Where [crate::GVideoUrl] plays the sound
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub enum AudioSink {
+    /// The output chosen by autoaudiosink, usually the default device of the system
+    #[default]
+    Auto,
+    /// The device with the [AudioOutput::id]
+    Device(String),
+}
+
+impl AudioSink {
+    fn make_element(&self) -> Result<gst::Element, IcedGStreamerError> {
+        let element = match self {
+            AudioSink::Auto => make_element("autoaudiosink")?,
+            AudioSink::Device(id) => monitored_devices()?
+                .into_iter()
+                .find(|device| device_id(device) == *id)
+                .ok_or_else(|| IcedGStreamerError::NoAudioDevice(id.clone()))?
+                .create_element(None)?,
+        };
+        element.set_property("name", AUDIO_OUTPUT_NAME);
+        Ok(element)
+    }
+}
+
+/// An audio output device of the system
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AudioOutput {
+    /// the id for [AudioSink::Device], it is kept while the device is plugged
+    pub id: String,
+    /// the name to show to the user
+    pub name: String,
+}
+
+impl AudioOutput {
+    fn new(device: &gst::Device) -> Self {
+        Self {
+            id: device_id(device),
+            name: device.display_name().into(),
+        }
+    }
+}
+
+/// A device is plugged or unplugged, see [audio_output_subscription]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AudioOutputEvent {
+    Added(AudioOutput),
+    Removed(AudioOutput),
+}
+
+/// the node name of pipewire or the device name of pulseaudio and alsa, the display name is used
+/// if the device has none of them
+fn device_id(device: &gst::Device) -> String {
+    device
+        .properties()
+        .and_then(|properties| {
+            ["node.name", "device.string", "device.name", "udev.id"]
+                .into_iter()
+                .find_map(|field| properties.get::<String>(field).ok())
+        })
+        .unwrap_or_else(|| device.display_name().into())
+}
+
+fn audio_output_monitor() -> Result<gst::DeviceMonitor, IcedGStreamerError> {
+    gst::init()?;
+    let monitor = gst::DeviceMonitor::new();
+    monitor.add_filter(Some(AUDIO_SINK_CLASS), None);
+    Ok(monitor)
+}
+
+fn monitored_devices() -> Result<Vec<gst::Device>, IcedGStreamerError> {
+    let monitor = audio_output_monitor()?;
+    monitor.start()?;
+    let devices = monitor.devices().into_iter().collect();
+    monitor.stop();
+    Ok(devices)
+}
+
+/// The audio output devices of the system, for [crate::GVideoUrl::set_audio_sink]
+pub fn audio_outputs() -> Result<Vec<AudioOutput>, IcedGStreamerError> {
+    Ok(monitored_devices()?.iter().map(AudioOutput::new).collect())
+}
+
+/// The audio output devices which are plugged or unplugged while the subscription is running
+pub fn audio_output_subscription() -> Subscription<AudioOutputEvent> {
+    Subscription::run(|| {
+        let monitor = audio_output_monitor().and_then(|monitor| {
+            monitor.start()?;
+            Ok(monitor)
+        });
+        let monitor = match monitor {
+            Ok(monitor) => monitor,
+            Err(err) => {
+                log::warn!("failed to monitor the audio outputs: {err}");
+                return stream::empty().boxed();
+            }
+        };
+        // the monitor is kept in the stream, so it runs as long as the subscription
+        monitor
+            .bus()
+            .stream()
+            .filter_map(move |message| {
+                let _ = &monitor;
+                future::ready(match message.view() {
+                    gst::MessageView::DeviceAdded(added) => {
+                        Some(AudioOutputEvent::Added(AudioOutput::new(&added.device())))
+                    }
+                    gst::MessageView::DeviceRemoved(removed) => Some(AudioOutputEvent::Removed(
+                        AudioOutput::new(&removed.device()),
+                    )),
+                    _ => None,
+                })
+            })
+            .boxed()
+    })
+}
+
+/// Replace the output of the audio sink of playbin, the pad before it is blocked while they are
+/// swapped, so no buffer reaches the removed output
+pub(crate) fn replace_audio_output(
+    audio_sink: &gst::Bin,
+    sink: &AudioSink,
+) -> Result<(), IcedGStreamerError> {
+    let old_output = audio_sink
+        .by_name(AUDIO_OUTPUT_NAME)
+        .ok_or(IcedGStreamerError::Cast)?;
+    let new_output = sink.make_element()?;
+    let src_pad = old_output
+        .static_pad("sink")
+        .and_then(|pad| pad.peer())
+        .ok_or(IcedGStreamerError::Cast)?;
+    let queue = src_pad.parent_element().ok_or(IcedGStreamerError::Cast)?;
+
+    // the pad only blocks when a buffer flows
+    if audio_sink.current_state() != gst::State::Playing {
+        return swap_audio_output(audio_sink, &queue, &old_output, &new_output);
+    }
+    let audio_sink = audio_sink.downgrade();
+    src_pad.add_probe(gst::PadProbeType::BLOCK_DOWNSTREAM, move |_, _| {
+        if let Some(audio_sink) = audio_sink.upgrade()
+            && let Err(err) = swap_audio_output(&audio_sink, &queue, &old_output, &new_output)
+        {
+            log::error!("failed to switch the audio output: {err}");
+        }
+        gst::PadProbeReturn::Remove
+    });
+    Ok(())
+}
+
+fn swap_audio_output(
+    audio_sink: &gst::Bin,
+    queue: &gst::Element,
+    old_output: &gst::Element,
+    new_output: &gst::Element,
+) -> Result<(), IcedGStreamerError> {
+    old_output.set_state(gst::State::Null)?;
+    audio_sink.remove(old_output)?;
+    audio_sink.add(new_output)?;
+    queue.link(new_output)?;
+    new_output.sync_state_with_parent()?;
+    Ok(())
+}
@@ -0,0 +1,304 @@
+use gst::prelude::*;
+use gstreamer as gst;
+use gstreamer::glib;
+use iced_futures::Subscription;
+use iced_futures::futures::channel::mpsc;
+use iced_futures::futures::{StreamExt, future};
+use std::hash::Hash;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, OnceLock, RwLock, Weak};
+use std::time::Duration;
+
+use crate::recording::{RECORDING_FINISHED_MESSAGE, SEGMENT_CLOSED_MESSAGE};
+use crate::{Position, RecordingState};
+
+/// What the bus of a [crate::GVideo] reports, received with [crate::GVideo::subscription]. The
+/// variants sent by the [crate::VideoPlayer] are only received with
+/// [crate::VideoPlayer::on_event], so every event is received once
+#[derive(Debug, Clone)]
+pub enum GVideoEvent {
+    Error(glib::Error),
+    Warning(glib::Error),
+    EndOfStream,
+    /// the state of the whole pipeline is changed
+    StateChanged(gst::State),
+    /// how much of the buffer is filled, in percent. A network stream should be paused until it
+    /// is 100
+    Buffering(i32),
+    Tags(gst::TagList),
+    /// the new duration, if it can be queried already
+    DurationChanged(Option<Duration>),
+    /// the clock is lost, the pipeline should be paused and played again to select a new one
+    ClockLost,
+    /// the latency of an element is changed, the pipeline should recalculate it
+    Latency,
+    /// a seek of the [crate::VideoPlayer] is done, with the new position
+    SeekCompleted(Option<Duration>),
+    /// the volume or the mute of the playbin is changed, the volume is linear
+    VolumeChanged {
+        volume: f64,
+        muted: bool,
+    },
+    /// a seek to the position is refused, or a frame can not be converted to the time because the
+    /// framerate is unknown
+    SeekFailed(Position),
+    /// the streams of the media, only reported by the elements which support stream selection
+    StreamsChanged(gst::StreamCollection),
+    /// the streams which are played now
+    StreamsSelected(gst::StreamCollection),
+    /// quality of service, how many buffers are processed and dropped by an element
+    Qos {
+        processed: u64,
+        dropped: u64,
+    },
+    /// the file of a recording is finished
+    RecordingFinished(PathBuf),
+    /// a segment of a segmented recording is finished
+    SegmentFinished(PathBuf),
+    /// the [crate::Playlist] plays another item, the index is in [crate::Playlist::items]
+    CurrentItemChanged {
+        index: usize,
+        uri: url::Url,
+    },
+    /// the size of the frames is changed, only sent by the [crate::VideoPlayer]
+    ResolutionChanged {
+        width: u32,
+        height: u32,
+    },
+    /// the position is changed, only sent by the [crate::VideoPlayer]
+    PositionChanged(Duration),
+    /// another subtitle is shown, or None if it is hidden, only sent by the [crate::VideoPlayer]
+    SubtitleChanged(Option<crate::SubtitleCue>),
+    /// the recording is started, paused, resumed or stopped, only sent by the
+    /// [crate::VideoPlayer]
+    RecordingStateChanged(RecordingState),
+}
+
+impl GVideoEvent {
+    /// The AsyncDone message with the seqnum of the last seek in the `state` ends the seek
+    pub(crate) fn from_message(
+        message: &gst::Message,
+        source: &gst::Bin,
+        state: &crate::State,
+    ) -> Option<Self> {
+        let position = || {
+            source
+                .query_position::<gst::ClockTime>()
+                .map(|time| Duration::from_nanos(time.nseconds()))
+        };
+        let location = |structure: &gst::StructureRef| {
+            structure.get::<String>("location").ok().map(PathBuf::from)
+        };
+        Some(match message.view() {
+            gst::MessageView::Error(err) => Self::Error(err.error()),
+            gst::MessageView::Warning(warning) => Self::Warning(warning.error()),
+            // the video is started again while looping
+            gst::MessageView::Eos(_) if state.looping => return None,
+            gst::MessageView::Eos(_) => Self::EndOfStream,
+            gst::MessageView::StateChanged(change)
+                if message.src() == Some(source.upcast_ref()) =>
+            {
+                Self::StateChanged(change.current())
+            }
+            // the playbin starts the next uri
+            gst::MessageView::StreamStart(_) if message.src() == Some(source.upcast_ref()) => {
+                let playlist = state.playlist.lock().unwrap();
+                Self::CurrentItemChanged {
+                    index: playlist.current_index()?,
+                    uri: playlist.current()?.clone(),
+                }
+            }
+            gst::MessageView::Buffering(buffering) => Self::Buffering(buffering.percent()),
+            gst::MessageView::Tag(tag) => Self::Tags(tag.tags()),
+            gst::MessageView::DurationChanged(_) => Self::DurationChanged(
+                source
+                    .query_duration::<gst::ClockTime>()
+                    .map(|time| Duration::from_nanos(time.nseconds())),
+            ),
+            gst::MessageView::ClockLost(_) => Self::ClockLost,
+            gst::MessageView::Latency(_) => Self::Latency,
+            gst::MessageView::AsyncDone(_) if Some(message.seqnum()) == state.seek_seqnum => {
+                Self::SeekCompleted(position())
+            }
+            gst::MessageView::StreamCollection(collection) => {
+                Self::StreamsChanged(collection.stream_collection())
+            }
+            gst::MessageView::StreamsSelected(selected) => {
+                Self::StreamsSelected(selected.stream_collection())
+            }
+            gst::MessageView::Qos(qos) => {
+                let (processed, dropped) = qos.stats();
+                Self::Qos {
+                    processed: u64::try_from(processed.value()).unwrap_or(0),
+                    dropped: u64::try_from(dropped.value()).unwrap_or(0),
+                }
+            }
+            gst::MessageView::Application(application) => {
+                let structure = application.structure()?;
+                match structure.name().as_str() {
+                    RECORDING_FINISHED_MESSAGE => Self::RecordingFinished(location(structure)?),
+                    VOLUME_CHANGED_MESSAGE => Self::VolumeChanged {
+                        volume: structure.get("volume").ok()?,
+                        muted: structure.get("muted").ok()?,
+                    },
+                    SEEK_FAILED_MESSAGE => Self::SeekFailed(
+                        match (structure.get::<u64>("frame"), structure.get::<u64>("time")) {
+                            (Ok(frame), _) => Position::Frame(frame),
+                            (_, Ok(time)) => Position::Time(Duration::from_nanos(time)),
+                            _ => return None,
+                        },
+                    ),
+                    _ => return None,
+                }
+            }
+            gst::MessageView::Element(element) => {
+                let structure = element.structure()?;
+                if structure.name() != SEGMENT_CLOSED_MESSAGE {
+                    return None;
+                }
+                Self::SegmentFinished(location(structure)?)
+            }
+            _ => return None,
+        })
+    }
+}
+
+/// The name of the application message posted when a seek fails
+const SEEK_FAILED_MESSAGE: &str = "gstreamer-iced-seek-failed";
+
+/// Report a failed seek on the bus, so the subscriptions receive it as well
+pub(crate) fn post_seek_failed(source: &gst::Bin, position: Position) {
+    let structure = match position {
+        Position::Time(time) => gst::Structure::builder(SEEK_FAILED_MESSAGE)
+            .field("time", time.as_nanos() as u64)
+            .build(),
+        Position::Frame(frame) => gst::Structure::builder(SEEK_FAILED_MESSAGE)
+            .field("frame", frame)
+            .build(),
+    };
+    let _ = source.post_message(
+        gst::message::Application::builder(structure)
+            .src(source)
+            .build(),
+    );
+}
+
+/// The name of the application message posted when the volume or the mute is changed
+const VOLUME_CHANGED_MESSAGE: &str = "gstreamer-iced-volume-changed";
+
+/// Report the volume of the playbin on the bus, it is called by the notify of its properties
+pub(crate) fn post_volume_changed(playbin: &gst::Element) {
+    let structure = gst::Structure::builder(VOLUME_CHANGED_MESSAGE)
+        .field("volume", playbin.property::<f64>("volume"))
+        .field("muted", playbin.property::<bool>("mute"))
+        .build();
+    let _ = playbin.post_message(
+        gst::message::Application::builder(structure)
+            .src(playbin)
+            .build(),
+    );
+}
+
+type Senders = Arc<Mutex<Vec<mpsc::UnboundedSender<gst::Message>>>>;
+type Handler = Arc<dyn Fn(&gst::Message) + Send + Sync>;
+
+/// Copy the messages of the bus to the subscriptions, they are still kept on the bus for the
+/// [crate::VideoPlayer]
+#[derive(Default)]
+pub(crate) struct BusEvents {
+    senders: Senders,
+    handlers: Arc<Mutex<Vec<Handler>>>,
+    forwarding: OnceLock<()>,
+}
+
+impl std::fmt::Debug for BusEvents {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BusEvents")
+            .field("senders", &self.senders)
+            .field("handlers", &self.handlers.lock().unwrap().len())
+            .finish()
+    }
+}
+
+impl BusEvents {
+    /// Call the handler with every message of the bus, in the thread which posts it, before the
+    /// message is copied to the subscriptions. The handlers are called in the order they are
+    /// added
+    pub(crate) fn watch(
+        &self,
+        bus: &gst::Bus,
+        handler: impl Fn(&gst::Message) + Send + Sync + 'static,
+    ) {
+        self.handlers.lock().unwrap().push(Arc::new(handler));
+        self.forward(bus);
+    }
+
+    /// The sync handler of the bus can only be set once, it calls the handlers of
+    /// [BusEvents::watch] and copies the message to the subscriptions
+    fn forward(&self, bus: &gst::Bus) {
+        self.forwarding.get_or_init(|| {
+            let senders = self.senders.clone();
+            let handlers = self.handlers.clone();
+            bus.set_sync_handler(move |_, message| {
+                // a handler may post a message itself, the lock is not held while it runs
+                let current = handlers.lock().unwrap().clone();
+                for handler in current {
+                    handler(message);
+                }
+                senders
+                    .lock()
+                    .unwrap()
+                    .retain(|sender| sender.unbounded_send(message.clone()).is_ok());
+                gst::BusSyncReply::Pass
+            });
+        });
+    }
+
+    pub(crate) fn subscription(
+        &self,
+        id: crate::id::Id,
+        bus: &gst::Bus,
+        source: &gst::Bin,
+        state: &Arc<RwLock<crate::State>>,
+    ) -> Subscription<GVideoEvent> {
+        self.forward(bus);
+        Subscription::run_with(
+            EventSource {
+                id,
+                senders: self.senders.clone(),
+                source: source.downgrade(),
+                state: Arc::downgrade(state),
+            },
+            |event_source| {
+                let (sender, receiver) = mpsc::unbounded();
+                event_source.senders.lock().unwrap().push(sender);
+                let source = event_source.source.clone();
+                let state = event_source.state.clone();
+                receiver.filter_map(move |message| {
+                    let event =
+                        source
+                            .upgrade()
+                            .zip(state.upgrade())
+                            .and_then(|(source, state)| {
+                                GVideoEvent::from_message(&message, &source, &state.read().unwrap())
+                            });
+                    future::ready(event)
+                })
+            },
+        )
+    }
+}
+
+/// The data of the subscription, it is identified by the id of the video
+struct EventSource {
+    id: crate::id::Id,
+    senders: Senders,
+    source: glib::WeakRef<gst::Bin>,
+    state: Weak<RwLock<crate::State>>,
+}
+
+impl Hash for EventSource {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
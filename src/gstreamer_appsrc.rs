@@ -0,0 +1,208 @@
+use super::recording::TEE_NAME;
+use super::{FrameData, GVideoInner, IcedGStreamerError, RecordingOptions};
+use gst::prelude::*;
+use gstreamer as gst;
+use gstreamer_app as gst_app;
+use gstreamer_video as gst_video;
+use std::{
+    path::Path,
+    sync::{Arc, Mutex, RwLock, atomic::AtomicBool},
+};
+
+/// The main container for a gstreamer task
+/// For frames pushed from rust code
+pub type GVideoAppSrc = GVideoInner<3>;
+
+/// the name of the appsrc inside the pipeline
+const APP_SRC_NAME: &str = "app_src";
+
+/// The pixel format of the frames pushed by [AppSrcHandle]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AppSrcFormat {
+    /// 4 bytes per pixel, tightly packed
+    Rgba,
+    /// a full size Y plane followed by a half size interleaved UV plane
+    Nv12,
+}
+
+impl AppSrcFormat {
+    fn video_format(&self) -> gst_video::VideoFormat {
+        match self {
+            Self::Rgba => gst_video::VideoFormat::Rgba,
+            Self::Nv12 => gst_video::VideoFormat::Nv12,
+        }
+    }
+
+    /// the offsets and the strides of the planes, the rows are tightly packed
+    fn layout(&self, width: u32, height: u32) -> (Vec<usize>, Vec<i32>) {
+        let width = width as usize;
+        match self {
+            Self::Rgba => (vec![0], vec![width as i32 * 4]),
+            Self::Nv12 => (
+                vec![0, width * height as usize],
+                vec![width as i32, width.div_ceil(2) as i32 * 2],
+            ),
+        }
+    }
+
+    /// how many bytes a frame of this size should take
+    pub fn frame_size(&self, width: u32, height: u32) -> usize {
+        let (width, height) = (width as usize, height as usize);
+        match self {
+            Self::Rgba => width * height * 4,
+            Self::Nv12 => width * height + width.div_ceil(2) * 2 * height.div_ceil(2),
+        }
+    }
+}
+
+/// A handle to push frames to a [GVideoAppSrc]
+/// It can be cloned and sent to another thread
+#[derive(Debug, Clone)]
+pub struct AppSrcHandle {
+    app_src: gst_app::AppSrc,
+}
+
+impl AppSrcHandle {
+    /// Push a frame, the caps will be renegotiated if the format or the size is changed
+    /// The timestamp is the running time of the frame, begin with zero
+    pub fn push_frame(
+        &self,
+        format: AppSrcFormat,
+        width: u32,
+        height: u32,
+        data: Vec<u8>,
+        timestamp: std::time::Duration,
+    ) -> Result<(), IcedGStreamerError> {
+        if data.len() != format.frame_size(width, height) {
+            return Err(IcedGStreamerError::FrameSize);
+        }
+        let caps = gst_video::VideoInfo::builder(format.video_format(), width, height)
+            .fps(gst::Fraction::new(0, 1))
+            .par(gst::Fraction::new(1, 1))
+            .build()?
+            .to_caps()?;
+        if self.app_src.caps().as_ref() != Some(&caps) {
+            self.app_src.set_caps(Some(&caps));
+        }
+
+        let mut buffer = gst::Buffer::from_mut_slice(data);
+        {
+            let buffer = buffer.get_mut().ok_or(IcedGStreamerError::Cast)?;
+            buffer.set_pts(gst::ClockTime::from_nseconds(timestamp.as_nanos() as _));
+            // the rows of the caps are aligned to 4 bytes, the meta tells the real strides
+            let (offsets, strides) = format.layout(width, height);
+            gst_video::VideoMeta::add_full(
+                buffer,
+                gst_video::VideoFrameFlags::empty(),
+                format.video_format(),
+                width,
+                height,
+                &offsets,
+                &strides,
+            )?;
+        }
+        self.app_src.push_buffer(buffer)?;
+        Ok(())
+    }
+
+    /// Tell the pipeline that no more frame will be pushed
+    pub fn end_of_stream(&self) -> Result<(), IcedGStreamerError> {
+        self.app_src.end_of_stream()?;
+        Ok(())
+    }
+}
+
+impl GVideoAppSrc {
+    /// get the [AppSrcHandle] to push frames
+    pub fn handle(&self) -> AppSrcHandle {
+        let app_src = self
+            .source
+            .by_name(APP_SRC_NAME)
+            .expect("appsrc should be in the pipeline")
+            .downcast::<gst_app::AppSrc>()
+            .expect("should be an appsrc");
+        AppSrcHandle { app_src }
+    }
+
+    fn app_src() -> gst_app::AppSrc {
+        gst_app::AppSrc::builder()
+            .name(APP_SRC_NAME)
+            .format(gst::Format::Time)
+            .is_live(true)
+            .do_timestamp(false)
+            .build()
+    }
+
+    /// Create a pipeline whose frames are pushed by [AppSrcHandle]
+    pub(crate) fn new_appsrc() -> Result<Self, IcedGStreamerError> {
+        gst::init()?;
+
+        let source = gst::Pipeline::new();
+        let app_src: gst::Element = Self::app_src().into();
+
+        let videoconvert = gst::ElementFactory::make("videoconvert").build()?;
+
+        let app_sink_caps = crate::app_sink_caps();
+
+        let app_sink: gst_app::AppSink = gst_app::AppSink::builder()
+            .name("app_sink")
+            .caps(&app_sink_caps)
+            .build();
+
+        let state = Arc::new(RwLock::new(crate::State::new()));
+
+        let upload_frame = Arc::new(AtomicBool::new(false));
+        let upload_frame_i = upload_frame.clone();
+        let frame = Arc::new(Mutex::new(None));
+        let frame_i = frame.clone();
+        app_sink.set_callbacks(
+            gst_app::AppSinkCallbacks::builder()
+                .new_sample(move |sink| {
+                    let sample = sink.pull_sample().map_err(|_| gst::FlowError::Eos)?;
+                    let data = FrameData::from_sample(&sample)?;
+
+                    upload_frame_i.store(true, std::sync::atomic::Ordering::SeqCst);
+                    *frame_i.lock().map_err(|_| gst::FlowError::Eos)? = Some(data);
+                    Ok(gst::FlowSuccess::Ok)
+                })
+                .build(),
+        );
+
+        let app_sink: gst::Element = app_sink.into();
+        let queue = gst::ElementFactory::make("queue")
+            .property("max-size-buffers", 50_u32)
+            .property("max-size-bytes", 0_u32)
+            .property("max-size-time", 0_u64)
+            .build()?;
+        // recordings are attached to the tee
+        let tee = gst::ElementFactory::make("tee").name(TEE_NAME).build()?;
+        source.add_many([&app_src, &tee, &queue, &videoconvert, &app_sink])?;
+
+        gst::Element::link_many([&app_src, &tee, &queue, &videoconvert, &app_sink])?;
+
+        source.set_state(gst::State::Playing)?;
+
+        Ok(Self {
+            bus: source.bus().unwrap(),
+            source: source.into(),
+            state,
+            upload_frame,
+            frame,
+            alive: Arc::new(AtomicBool::new(true)),
+            id: crate::id::Id::unique(),
+            pending_events: RwLock::new(vec![]),
+            recording: Mutex::new(None),
+            events: Default::default(),
+        })
+    }
+
+    /// Create a pipeline whose frames are pushed by [AppSrcHandle], and record them to a file
+    pub(crate) fn new_appsrc_and_record<P: AsRef<Path>>(
+        file: P,
+        options: &RecordingOptions,
+    ) -> Result<Self, IcedGStreamerError> {
+        let video = Self::new_appsrc()?;
+        video.start_recording(file, options)?;
+        Ok(video)
+    }
+}
@@ -0,0 +1,70 @@
+use super::{FrameData, GVideoInner, IcedGStreamerError};
+use gst::prelude::*;
+use gstreamer as gst;
+use gstreamer_app as gst_app;
+use std::sync::{Arc, Mutex, RwLock, atomic::AtomicBool};
+
+/// The main container for a gstreamer task
+/// For a custom gst-launch description
+pub type GVideoPipeline = GVideoInner<2>;
+
+/// the name of the appsink appended to the description
+const APP_SINK_NAME: &str = "gstreamer_iced_app_sink";
+
+impl GVideoPipeline {
+    /// Accept a gst-launch description, like `videotestsrc ! video/x-raw,width=640`. The last
+    /// element of the description should expose a raw video src pad, the appsink used by the
+    /// [crate::VideoPlayer] will be appended after it.
+    pub(crate) fn new_pipeline(description: &str) -> Result<Self, IcedGStreamerError> {
+        gst::init()?;
+
+        let description =
+            format!("{description} ! videoconvert ! videoscale ! appsink name={APP_SINK_NAME}");
+        let source = gst::parse::launch(&description)?
+            .downcast::<gst::Bin>()
+            .map_err(|_| IcedGStreamerError::Cast)?;
+
+        let app_sink = source
+            .by_name(APP_SINK_NAME)
+            .ok_or(IcedGStreamerError::Cast)?
+            .downcast::<gst_app::AppSink>()
+            .map_err(|_| IcedGStreamerError::Cast)?;
+
+        let app_sink_caps = crate::app_sink_caps();
+        app_sink.set_caps(Some(&app_sink_caps));
+
+        let state = Arc::new(RwLock::new(crate::State::new()));
+
+        let upload_frame = Arc::new(AtomicBool::new(false));
+        let upload_frame_i = upload_frame.clone();
+        let frame = Arc::new(Mutex::new(None));
+        let frame_i = frame.clone();
+        app_sink.set_callbacks(
+            gst_app::AppSinkCallbacks::builder()
+                .new_sample(move |sink| {
+                    let sample = sink.pull_sample().map_err(|_| gst::FlowError::Eos)?;
+                    let data = FrameData::from_sample(&sample)?;
+
+                    upload_frame_i.store(true, std::sync::atomic::Ordering::SeqCst);
+                    *frame_i.lock().map_err(|_| gst::FlowError::Eos)? = Some(data);
+                    Ok(gst::FlowSuccess::Ok)
+                })
+                .build(),
+        );
+
+        source.set_state(gst::State::Playing)?;
+
+        Ok(Self {
+            bus: source.bus().ok_or(IcedGStreamerError::Bus)?,
+            source,
+            state,
+            upload_frame,
+            frame,
+            alive: Arc::new(AtomicBool::new(true)),
+            id: crate::id::Id::unique(),
+            pending_events: RwLock::new(vec![]),
+            recording: Mutex::new(None),
+            events: Default::default(),
+        })
+    }
+}
@@ -1,19 +1,132 @@
 use gst::prelude::*;
 use gstreamer as gst;
+use gstreamer::glib;
 use gstreamer_app as gst_app;
+use gstreamer_audio as gst_audio;
+use gstreamer_video as gst_video;
 use std::path::Path;
 use std::sync::atomic::AtomicBool;
 use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+use super::recording::TEE_NAME;
+use super::video_player::{loop_start, seek_event};
+use super::{
+    AudioSink, FrameData, GVideoInner, GsEvent, IcedGStreamerError, Playlist, Position,
+    RecordingOptions, RepeatMode, SeekMode, SubtitleCue, SubtitleTrack,
+};
+
+/// the slowest playback rate
+const MIN_RATE: f64 = 0.25;
+/// the fastest playback rate
+const MAX_RATE: f64 = 4.;
+
+/// An audio stream of the media, the fields are None if the tags or the caps do not have them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AudioTrack {
+    /// the index for [GVideoUrl::select_audio_track]
+    pub index: usize,
+    pub language: Option<String>,
+    pub codec: Option<String>,
+    pub channels: Option<i32>,
+    pub title: Option<String>,
+}
 
-use super::{FrameData, GVideoInner, GsEvent, IcedGStreamerError, Position};
+impl AudioTrack {
+    fn new(index: usize, tags: Option<&gst::TagListRef>, caps: Option<&gst::CapsRef>) -> Self {
+        Self {
+            index,
+            language: tags
+                .and_then(|tags| {
+                    tags.get::<gst::tags::LanguageName>()
+                        .or_else(|| tags.get::<gst::tags::LanguageCode>())
+                })
+                .map(|language| language.get().to_owned()),
+            codec: tags
+                .and_then(|tags| {
+                    tags.get::<gst::tags::AudioCodec>()
+                        .or_else(|| tags.get::<gst::tags::Codec>())
+                })
+                .map(|codec| codec.get().to_owned()),
+            channels: caps
+                .and_then(|caps| caps.structure(0))
+                .and_then(|structure| structure.get::<i32>("channels").ok()),
+            title: tags
+                .and_then(|tags| tags.get::<gst::tags::Title>())
+                .map(|title| title.get().to_owned()),
+        }
+    }
+}
+
+fn is_type(stream_type: gst::StreamType) -> impl Fn(&gst::Stream) -> bool {
+    move |stream| stream.stream_type().contains(stream_type)
+}
+
+/// The stream collection of playbin3 and the ids of the streams it plays, they are stored by the
+/// bus handler of [GVideoUrl], so the tracks can be selected without a [crate::VideoPlayer]
+#[derive(Debug, Default)]
+pub(crate) struct Streams {
+    collection: Option<gst::StreamCollection>,
+    selected: Vec<String>,
+}
+
+impl Streams {
+    /// The ids of the streams to play after the stream of the type with the id is selected. The
+    /// streams of the other types stay the same, their default stream is kept if nothing is
+    /// selected yet
+    fn select(&self, stream_type: gst::StreamType, id: &str) -> Vec<String> {
+        let Some(collection) = &self.collection else {
+            return vec![id.to_owned()];
+        };
+        let mut streams = vec![id.to_owned()];
+        for other_type in [
+            gst::StreamType::AUDIO,
+            gst::StreamType::VIDEO,
+            gst::StreamType::TEXT,
+        ] {
+            if other_type == stream_type {
+                continue;
+            }
+            let of_type: Vec<gst::Stream> = collection.iter().filter(is_type(other_type)).collect();
+            let selected: Vec<String> = of_type
+                .iter()
+                .filter_map(|stream| stream.stream_id())
+                .map(String::from)
+                .filter(|id| self.selected.contains(id))
+                .collect();
+            if !self.selected.is_empty() {
+                streams.extend(selected);
+                continue;
+            }
+            // decodebin3 plays the stream flagged to be selected, or the first one of the type
+            let default = of_type
+                .iter()
+                .find(|stream| stream.stream_flags().contains(gst::StreamFlags::SELECT))
+                .or(of_type.first())
+                .and_then(|stream| stream.stream_id());
+            streams.extend(default.map(String::from));
+        }
+        streams
+    }
+}
 
 /// The main container for a gstreamer task
 /// For playbin url
 pub type GVideoUrl = GVideoInner<0>;
 
 impl GVideoUrl {
-    /// Seak to a position
+    /// Seak to a position, same as [GVideoUrl::seek_with] with [SeekMode::Accurate]
     pub fn seek<T>(&self, position: T)
+    where
+        T: Into<Position>,
+    {
+        self.seek_with(position, SeekMode::default());
+    }
+
+    /// Seek to a position, the [SeekMode] decides if it lands exactly on it or on a keyframe.
+    /// A frame is converted to the time with the framerate of the video.
+    /// [crate::GVideoEvent::SeekCompleted] or [crate::GVideoEvent::SeekFailed] reports the result
+    pub fn seek_with<T>(&self, position: T, mode: SeekMode)
     where
         T: Into<Position>,
     {
@@ -22,127 +135,359 @@ impl GVideoUrl {
             self.set_state(gst::State::Playing);
         }
         let mut pending_events = self.pending_events.write().unwrap();
-        pending_events.push(GsEvent::Jump(pos));
+        pending_events.push(GsEvent::Jump(pos, mode));
     }
 
-    /// accept url like from local or from http
-    pub(crate) fn new_url(url: &url::Url, islive: bool) -> Result<Self, IcedGStreamerError> {
-        gst::init()?;
+    /// the framerate negotiated with the video sink, None before the video is prerolled or if
+    /// the framerate is variable
+    pub fn framerate(&self) -> Option<gst::Fraction> {
+        let caps = self
+            .source
+            .property::<Option<gst::Element>>("video-sink")
+            .and_then(|sink| sink.downcast::<gst::Bin>().ok())?
+            .by_name("app_sink")?
+            .static_pad("sink")?
+            .current_caps()?;
+        let framerate = gst_video::VideoInfo::from_caps(&caps).ok()?.fps();
+        (framerate.numer() > 0).then_some(framerate)
+    }
 
-        let video_sink = gst::Bin::new();
-        let videoscale = gst::ElementFactory::make("videoscale").build()?;
-        let videoconvert = gst::ElementFactory::make("videoconvert").build()?;
+    /// Set the playback rate, its magnitude is clamped from 0.25 to 4, a negative rate plays the
+    /// video backward. The audio keeps its pitch if scaletempo is installed.
+    /// A rate of zero, NaN or infinity is rejected with [IcedGStreamerError::InvalidRate]
+    pub fn set_rate(&self, rate: f64) -> Result<(), IcedGStreamerError> {
+        if !rate.is_finite() || rate == 0. {
+            return Err(IcedGStreamerError::InvalidRate(rate));
+        }
+        let rate = rate.signum() * rate.abs().clamp(MIN_RATE, MAX_RATE);
+        if self.play_state() == gst::State::Null {
+            self.set_state(gst::State::Playing);
+        }
+        let mut pending_events = self.pending_events.write().unwrap();
+        pending_events.push(GsEvent::Rate(rate));
+        Ok(())
+    }
 
-        let app_sink_caps = gst::Caps::builder("video/x-raw")
-            .field("format", "NV12")
-            .field("pixel-aspect-ratio", gst::Fraction::new(1, 1))
-            .build();
+    /// Pause the video and move n frames forward
+    pub fn step_forward(&self, frames: u32) {
+        self.step(frames as i64);
+    }
 
-        let app_sink: gst_app::AppSink = gst_app::AppSink::builder()
-            .name("my_sink")
-            .caps(&app_sink_caps)
-            .build();
+    /// Pause the video and move n frames backward, the rate becomes negative until it is set
+    /// again with [GVideoUrl::set_rate]
+    pub fn step_backward(&self, frames: u32) {
+        self.step(-(frames as i64));
+    }
 
-        let state = Arc::new(RwLock::new(
-            crate::State::new().with_try_get_duration(!islive),
-        ));
+    fn step(&self, frames: i64) {
+        if frames == 0 {
+            return;
+        }
+        self.set_state(gst::State::Paused);
+        let mut pending_events = self.pending_events.write().unwrap();
+        pending_events.push(GsEvent::Step(frames));
+    }
 
-        let upload_frame = Arc::new(AtomicBool::new(false));
-        let upload_frame_i = upload_frame.clone();
-        let frame = Arc::new(Mutex::new(None));
-        let frame_i = frame.clone();
-        app_sink.set_callbacks(
-            gst_app::AppSinkCallbacks::builder()
-                .new_sample(move |sink| {
-                    let sample = sink.pull_sample().map_err(|_| gst::FlowError::Eos)?;
-                    let buffer = sample.buffer().ok_or(gst::FlowError::Error)?;
-                    let map = buffer.map_readable().map_err(|_| gst::FlowError::Error)?;
+    /// Start the video again when it ends, without a black frame in between. The end of stream
+    /// is not reported while looping. Disabling it also removes the range of
+    /// [GVideoUrl::set_loop_range]
+    pub fn set_looping(&self, looping: bool) {
+        {
+            let mut state = self.state.write().unwrap();
+            state.looping = looping;
+            if !looping {
+                state.loop_range = None;
+            }
+        }
+        self.push_loop_event();
+    }
 
-                    let caps = sample.caps().ok_or(gst::FlowError::Error)?;
-                    let s = caps.structure(0).ok_or(gst::FlowError::Error)?;
-                    let width = s.get::<i32>("width").map_err(|_| gst::FlowError::Error)?;
-                    let height = s.get::<i32>("height").map_err(|_| gst::FlowError::Error)?;
+    /// Loop the part from a to b, it enables looping
+    pub fn set_loop_range(&self, a: Duration, b: Duration) {
+        {
+            let mut state = self.state.write().unwrap();
+            state.looping = true;
+            state.loop_range = Some((a.min(b), a.max(b)));
+        }
+        self.push_loop_event();
+    }
 
-                    upload_frame_i.store(true, std::sync::atomic::Ordering::SeqCst);
-                    let data = FrameData {
-                        width: width as _,
-                        height: height as _,
-                        pixels: map.as_slice().to_owned(),
-                    };
-                    *frame_i.lock().map_err(|_| gst::FlowError::Eos)? = Some(data);
+    /// if the video starts again when it ends
+    pub fn looping(&self) -> bool {
+        let state = self.state.read().unwrap();
+        state.looping
+    }
 
-                    Ok(gst::FlowSuccess::Ok)
-                })
-                .build(),
-        );
+    fn push_loop_event(&self) {
+        if self.play_state() == gst::State::Null {
+            self.set_state(gst::State::Playing);
+        }
+        let mut pending_events = self.pending_events.write().unwrap();
+        pending_events.push(GsEvent::Loop);
+    }
 
-        let app_sink: gst::Element = app_sink.into();
+    /// Replace the playlist and play its current item, the next items follow without a gap.
+    /// [crate::GVideoEvent::CurrentItemChanged] is sent when an item starts
+    pub fn set_playlist(&self, playlist: Playlist) {
+        let uri = playlist.current().cloned();
+        *self.playlist().lock().unwrap() = playlist;
+        if let Some(uri) = uri {
+            self.play_uri(&uri);
+        }
+    }
 
-        video_sink.add_many([&videoscale, &videoconvert, &app_sink])?;
-        gst::Element::link_many([&videoscale, &videoconvert, &app_sink])?;
+    /// A copy of the playlist, with the current item
+    pub fn playlist_snapshot(&self) -> Playlist {
+        self.playlist().lock().unwrap().clone()
+    }
 
-        let staticpad = videoscale.static_pad("sink").unwrap();
-        let sinkgost = gst::GhostPad::builder_with_target(&staticpad)?.build();
-        sinkgost.set_active(true)?;
-        video_sink.add_pad(&sinkgost)?;
+    /// Play the next item of the playlist, [RepeatMode::All] starts with the first one again
+    pub fn next(&self) {
+        let uri = self.playlist().lock().unwrap().next(false).cloned();
+        if let Some(uri) = uri {
+            self.play_uri(&uri);
+        }
+    }
 
-        let videosource = gst::ElementFactory::make("playbin")
-            .property("uri", url.as_str())
-            .property("video-sink", video_sink.to_value())
-            .build()?;
+    /// Play the previous item of the playlist
+    pub fn previous(&self) {
+        let uri = self.playlist().lock().unwrap().previous().cloned();
+        if let Some(uri) = uri {
+            self.play_uri(&uri);
+        }
+    }
 
-        let source = videosource.downcast::<gst::Bin>().unwrap();
+    /// Play the item at the index of [Playlist::items]
+    pub fn jump_to(&self, index: usize) {
+        let uri = self.playlist().lock().unwrap().jump(index).cloned();
+        if let Some(uri) = uri {
+            self.play_uri(&uri);
+        }
+    }
 
-        Ok(Self {
-            bus: source.bus().unwrap(),
-            source,
-            state,
-            upload_frame,
-            frame,
-            alive: Arc::new(AtomicBool::new(true)),
-            id: crate::id::Id::unique(),
-            pending_events: RwLock::new(vec![]),
-        })
+    /// Play the playlist in a random order
+    pub fn set_shuffle(&self, shuffle: bool) {
+        self.playlist().lock().unwrap().set_shuffle(shuffle);
     }
-    pub(crate) fn new_url_and_record<P: AsRef<Path>>(
-        url: &url::Url,
-        islive: bool,
-        file: P,
-    ) -> Result<Self, IcedGStreamerError> {
-        gst::init()?;
-        let p = file.as_ref();
 
-        let extension = p.extension().ok_or(IcedGStreamerError::NoExtension)?;
+    /// What happens at the end of an item
+    pub fn set_repeat(&self, repeat: RepeatMode) {
+        self.playlist().lock().unwrap().set_repeat(repeat);
+    }
 
-        let encoder = if extension == "flv" {
-            gst::ElementFactory::make("flvmux").build()?
-        } else if extension == "avi" {
-            gst::ElementFactory::make("avimux").build()?
-        } else if extension == "mp4" {
-            gst::ElementFactory::make("mp4mux").build()?
+    fn playlist(&self) -> Arc<Mutex<Playlist>> {
+        self.state.read().unwrap().playlist.clone()
+    }
+
+    fn streams(&self) -> Arc<Mutex<Streams>> {
+        self.state.read().unwrap().streams.clone()
+    }
+
+    /// the uri can only be changed in the Ready state, the queued uri is replaced too
+    fn play_uri(&self, uri: &url::Url) {
+        if let Err(err) = self.source.set_state(gst::State::Ready) {
+            log::warn!("failed to stop the current item: {err}");
+            return;
+        }
+        self.source.set_property("uri", uri.as_str());
+        self.set_state(gst::State::Playing);
+    }
+
+    /// The audio streams of the media. With playbin3 they are taken from the stream collection
+    pub fn audio_tracks(&self) -> Vec<AudioTrack> {
+        if let Some(collection) = &self.streams().lock().unwrap().collection {
+            return collection
+                .iter()
+                .filter(is_type(gst::StreamType::AUDIO))
+                .enumerate()
+                .map(|(index, stream)| {
+                    AudioTrack::new(index, stream.tags().as_deref(), stream.caps().as_deref())
+                })
+                .collect();
+        }
+        (0..self.source.property::<i32>("n-audio"))
+            .map(|index| {
+                let tags = self
+                    .source
+                    .emit_by_name::<Option<gst::TagList>>("get-audio-tags", &[&index]);
+                let caps = self
+                    .source
+                    .emit_by_name::<Option<gst::Pad>>("get-audio-pad", &[&index])
+                    .and_then(|pad| pad.current_caps());
+                AudioTrack::new(index as usize, tags.as_deref(), caps.as_deref())
+            })
+            .collect()
+    }
+
+    /// Play the audio track at the index of [GVideoUrl::audio_tracks], the other streams are
+    /// kept
+    pub fn select_audio_track(&self, index: usize) -> Result<(), IcedGStreamerError> {
+        if let Some(selected) = self.select_stream(gst::StreamType::AUDIO, index) {
+            return if selected {
+                Ok(())
+            } else {
+                Err(IcedGStreamerError::NoAudioTrack(index))
+            };
+        }
+        let index = i32::try_from(index)
+            .ok()
+            .filter(|&index| index < self.source.property::<i32>("n-audio"))
+            .ok_or(IcedGStreamerError::NoAudioTrack(index))?;
+        self.source.set_property("current-audio", index);
+        Ok(())
+    }
+
+    /// The subtitle streams of the media. With playbin3 they are taken from the stream
+    /// collection
+    pub fn subtitle_tracks(&self) -> Vec<SubtitleTrack> {
+        if let Some(collection) = &self.streams().lock().unwrap().collection {
+            return collection
+                .iter()
+                .filter(is_type(gst::StreamType::TEXT))
+                .enumerate()
+                .map(|(index, stream)| SubtitleTrack::new(index, stream.tags().as_deref()))
+                .collect();
+        }
+        (0..self.source.property::<i32>("n-text"))
+            .map(|index| {
+                let tags = self
+                    .source
+                    .emit_by_name::<Option<gst::TagList>>("get-text-tags", &[&index]);
+                SubtitleTrack::new(index as usize, tags.as_deref())
+            })
+            .collect()
+    }
+
+    /// Show the subtitle track at the index of [GVideoUrl::subtitle_tracks], the other streams
+    /// are kept
+    pub fn select_subtitle_track(&self, index: usize) -> Result<(), IcedGStreamerError> {
+        if let Some(selected) = self.select_stream(gst::StreamType::TEXT, index) {
+            return if selected {
+                Ok(())
+            } else {
+                Err(IcedGStreamerError::NoSubtitleTrack(index))
+            };
+        }
+        let index = i32::try_from(index)
+            .ok()
+            .filter(|&index| index < self.source.property::<i32>("n-text"))
+            .ok_or(IcedGStreamerError::NoSubtitleTrack(index))?;
+        self.source.set_property("current-text", index);
+        Ok(())
+    }
+
+    /// Show or hide the subtitles, the [crate::VideoPlayer] draws them over the video
+    pub fn set_subtitles_enabled(&self, enabled: bool) {
+        let flags = self.source.property_value("flags");
+        let Some(class) = glib::FlagsClass::with_type(flags.type_()) else {
+            return;
+        };
+        let flags = if enabled {
+            class.set_by_nick(flags, "text")
         } else {
-            return Err(IcedGStreamerError::UnsupportedExtension);
+            class.unset_by_nick(flags, "text")
         };
+        if let Ok(flags) = flags {
+            self.source.set_property_from_value("flags", &flags);
+        }
+        if !enabled {
+            *self.subtitle_cue().lock().unwrap() = None;
+        }
+    }
+
+    /// if the subtitles are shown
+    pub fn subtitles_enabled(&self) -> bool {
+        let flags = self.source.property_value("flags");
+        glib::FlagsClass::with_type(flags.type_())
+            .is_some_and(|class| class.is_set_by_nick(&flags, "text"))
+    }
+
+    /// The subtitle shown at the current position, None if the subtitles are disabled
+    pub fn subtitle(&self) -> Option<SubtitleCue> {
+        self.subtitle_with_state(&self.state.read().unwrap())
+    }
+
+    /// same as [GVideoUrl::subtitle], for the callers which hold the lock of the state
+    pub(crate) fn subtitle_with_state(&self, state: &crate::State) -> Option<SubtitleCue> {
+        if !self.subtitles_enabled() {
+            return None;
+        }
+        if !state.external_subtitles.is_empty() {
+            return crate::subtitles::cue_at(&state.external_subtitles, state.position);
+        }
+        let cue = state.subtitle.lock().unwrap().clone()?;
+        cue.is_shown_at(state.position).then_some(cue)
+    }
+
+    /// Show the subtitles of a file instead of the ones of the media. SRT and WebVTT files are
+    /// parsed by [crate::parse_subtitles], the other formats are given to playbin as `suburi`,
+    /// which restarts the video
+    pub fn set_external_subtitles<P: AsRef<Path>>(
+        &self,
+        path: P,
+    ) -> Result<(), IcedGStreamerError> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase);
+        if matches!(extension.as_deref(), Some("srt" | "vtt")) {
+            let cues = crate::parse_subtitles(&std::fs::read_to_string(path)?);
+            self.state.write().unwrap().external_subtitles = cues;
+            return Ok(());
+        }
+        let path = path.canonicalize()?;
+        let uri = url::Url::from_file_path(&path).map_err(|_| IcedGStreamerError::Uri)?;
+        self.state.write().unwrap().external_subtitles.clear();
+        self.source.set_state(gst::State::Ready)?;
+        self.source.set_property("suburi", uri.as_str());
+        self.set_state(gst::State::Playing);
+        Ok(())
+    }
+
+    fn subtitle_cue(&self) -> Arc<Mutex<Option<SubtitleCue>>> {
+        self.state.read().unwrap().subtitle.clone()
+    }
+
+    /// Select the stream of the type at the index in the stream collection, the selected streams
+    /// of the other types are kept. None without a stream collection
+    fn select_stream(&self, stream_type: gst::StreamType, index: usize) -> Option<bool> {
+        let streams = {
+            let streams = self.streams();
+            let streams = streams.lock().unwrap();
+            let Some(id) = streams
+                .collection
+                .as_ref()?
+                .iter()
+                .filter(is_type(stream_type))
+                .nth(index)
+                .and_then(|stream| stream.stream_id())
+            else {
+                return Some(false);
+            };
+            streams.select(stream_type, &id)
+        };
+        // the lock is released first, the StreamsSelected may be posted while the event is sent
+        Some(self.source.send_event(gst::event::SelectStreams::new(
+            streams.iter().map(String::as_str),
+        )))
+    }
+
+    /// the playback rate, negative when playing backward
+    pub fn rate(&self) -> f64 {
+        let state = self.state.read().unwrap();
+        state.rate
+    }
+
+    /// accept url like from local or from http
+    pub(crate) fn new_url(url: &url::Url, islive: bool) -> Result<Self, IcedGStreamerError> {
+        gst::init()?;
 
         let video_sink = gst::Bin::new();
         let videoscale = gst::ElementFactory::make("videoscale").build()?;
-        let videoconvert1 = gst::ElementFactory::make("videoconvert")
-            .name("videoconvert1")
-            .build()?;
-        let videoconvert2 = gst::ElementFactory::make("videoconvert")
-            .name("videoconvert2")
-            .build()?;
-        let x264enc = gst::ElementFactory::make("x264enc")
-            .property_from_str("tune", "zerolatency")
-            .build()?;
-        let filesink = gst::ElementFactory::make("filesink")
-            .property("location", p.to_str().unwrap())
-            .build()?;
+        let videoconvert = gst::ElementFactory::make("videoconvert").build()?;
 
-        let app_sink_caps = gst::Caps::builder("video/x-raw")
-            .field("format", "NV12")
-            .field("pixel-aspect-ratio", gst::Fraction::new(1, 1))
-            .build();
+        let app_sink_caps = crate::app_sink_caps();
 
         let app_sink: gst_app::AppSink = gst_app::AppSink::builder()
             .name("app_sink")
@@ -152,77 +497,91 @@ impl GVideoUrl {
         let state = Arc::new(RwLock::new(
             crate::State::new().with_try_get_duration(!islive),
         ));
+
         let upload_frame = Arc::new(AtomicBool::new(false));
         let upload_frame_i = upload_frame.clone();
+        let upload_frame_p = upload_frame.clone();
         let frame = Arc::new(Mutex::new(None));
         let frame_i = frame.clone();
+        let frame_p = frame.clone();
         app_sink.set_callbacks(
             gst_app::AppSinkCallbacks::builder()
                 .new_sample(move |sink| {
                     let sample = sink.pull_sample().map_err(|_| gst::FlowError::Eos)?;
-                    let buffer = sample.buffer().ok_or(gst::FlowError::Error)?;
-                    let map = buffer.map_readable().map_err(|_| gst::FlowError::Error)?;
-
-                    let caps = sample.caps().ok_or(gst::FlowError::Error)?;
-                    let s = caps.structure(0).ok_or(gst::FlowError::Error)?;
-                    let width = s.get::<i32>("width").map_err(|_| gst::FlowError::Error)?;
-                    let height = s.get::<i32>("height").map_err(|_| gst::FlowError::Error)?;
+                    let data = FrameData::from_sample(&sample)?;
 
                     upload_frame_i.store(true, std::sync::atomic::Ordering::SeqCst);
-                    let data = FrameData {
-                        width: width as _,
-                        height: height as _,
-                        pixels: map.as_slice().to_owned(),
-                    };
                     *frame_i.lock().map_err(|_| gst::FlowError::Eos)? = Some(data);
+
+                    Ok(gst::FlowSuccess::Ok)
+                })
+                // the frames stepped or seeked to while paused are only prerolled
+                .new_preroll(move |sink| {
+                    let sample = sink.pull_preroll().map_err(|_| gst::FlowError::Eos)?;
+                    let data = FrameData::from_sample(&sample)?;
+
+                    upload_frame_p.store(true, std::sync::atomic::Ordering::SeqCst);
+                    *frame_p.lock().map_err(|_| gst::FlowError::Eos)? = Some(data);
+
                     Ok(gst::FlowSuccess::Ok)
                 })
                 .build(),
         );
 
-        let app_sink: gst::Element = app_sink.clone().into();
-        let queue1 = gst::ElementFactory::make("queue")
-            .name("queue1")
-            .property("max-size-buffers", 50_u32)
-            .property("max-size-bytes", 0_u32)
-            .property("max-size-time", 0_u64)
-            .build()?;
-        let queue2 = gst::ElementFactory::make("queue")
-            .name("queue2")
+        let app_sink: gst::Element = app_sink.into();
+        let queue = gst::ElementFactory::make("queue")
             .property("max-size-buffers", 50_u32)
             .property("max-size-bytes", 0_u32)
             .property("max-size-time", 0_u64)
             .build()?;
-        let tee = gst::ElementFactory::make("tee").name("tee").build()?;
-        video_sink.add_many([
-            &videoscale,
-            &tee,
-            &queue1,
-            &queue2,
-            &videoconvert1,
-            &app_sink,
-            &videoconvert2,
-            &x264enc,
-            &encoder,
-            &filesink,
-        ])?;
-        gst::Element::link_many([&videoscale, &tee])?;
-        gst::Element::link_many([&tee, &queue1, &videoconvert1, &app_sink])?;
-        gst::Element::link_many([&tee, &queue2, &videoconvert2, &x264enc, &encoder, &filesink])?;
+        // recordings are attached to the tee
+        let tee = gst::ElementFactory::make("tee").name(TEE_NAME).build()?;
+
+        video_sink.add_many([&videoscale, &tee, &queue, &videoconvert, &app_sink])?;
+        gst::Element::link_many([&videoscale, &tee, &queue, &videoconvert, &app_sink])?;
 
         let staticpad = videoscale.static_pad("sink").unwrap();
         let sinkgost = gst::GhostPad::builder_with_target(&staticpad)?.build();
         sinkgost.set_active(true)?;
         video_sink.add_pad(&sinkgost)?;
 
+        let audio_sink = crate::recording::audio_sink_bin()?;
+
+        let text_sink = crate::subtitles::text_sink(&state.read().unwrap().subtitle);
+
         let videosource = gst::ElementFactory::make("playbin")
             .property("uri", url.as_str())
             .property("video-sink", video_sink.to_value())
+            .property("audio-sink", audio_sink.to_value())
+            .property("text-sink", text_sink.to_value())
             .build()?;
+        // keep the pitch of the audio when the rate is changed
+        if let Ok(scaletempo) = gst::ElementFactory::make("scaletempo").build() {
+            videosource.set_property("audio-filter", scaletempo.to_value());
+        }
+
+        // the next item of the playlist is queued, so it follows without a gap. It is still the
+        // current item until it starts, see GVideoUrl::watch_item_start
+        let playlist = state.read().unwrap().playlist.clone();
+        videosource.connect("about-to-finish", false, move |values| {
+            let playbin = values[0].get::<gst::Element>().unwrap();
+            if let Some(uri) = playlist.lock().unwrap().queue_next(true) {
+                playbin.set_property("uri", uri.as_str());
+            }
+            None
+        });
+
+        // the changes of the volume are reported on the bus, also the ones of the sound server
+        videosource.connect_notify(Some("volume"), |playbin, _| {
+            crate::events::post_volume_changed(playbin)
+        });
+        videosource.connect_notify(Some("mute"), |playbin, _| {
+            crate::events::post_volume_changed(playbin)
+        });
 
         let source = videosource.downcast::<gst::Bin>().unwrap();
 
-        Ok(Self {
+        let video = Self {
             bus: source.bus().unwrap(),
             source,
             state,
@@ -231,7 +590,92 @@ impl GVideoUrl {
             alive: Arc::new(AtomicBool::new(true)),
             id: crate::id::Id::unique(),
             pending_events: RwLock::new(vec![]),
-        })
+            recording: Mutex::new(None),
+            events: Default::default(),
+        };
+        video.watch_loop_end();
+        video.watch_item_start();
+        video.watch_streams();
+        Ok(video)
+    }
+
+    /// Store the stream collection and the selected streams of playbin3. They are not stored in
+    /// the state, the thread posting them may hold a stream lock a seek waits for
+    fn watch_streams(&self) {
+        let streams = self.streams();
+        self.events
+            .watch(&self.bus, move |message| match message.view() {
+                gst::MessageView::StreamCollection(collection) => {
+                    let mut streams = streams.lock().unwrap();
+                    streams.collection = Some(collection.stream_collection());
+                    streams.selected.clear();
+                }
+                gst::MessageView::StreamsSelected(selected) => {
+                    streams.lock().unwrap().selected = selected
+                        .streams()
+                        .filter_map(|stream| stream.stream_id())
+                        .map(String::from)
+                        .collect();
+                }
+                _ => {}
+            });
+    }
+
+    /// The queued item of the playlist becomes the current one when its stream starts, before
+    /// [crate::GVideoEvent::CurrentItemChanged] is made from the message
+    fn watch_item_start(&self) {
+        let playbin = self.source.downgrade();
+        let playlist = self.playlist();
+        self.events.watch(&self.bus, move |message| {
+            if let gst::MessageView::StreamStart(_) = message.view()
+                && let Some(playbin) = playbin.upgrade()
+                && message.src() == Some(playbin.upcast_ref())
+            {
+                playlist.lock().unwrap().start_queued();
+            }
+        });
+    }
+
+    /// Start the next loop when the end is reached, also when no [crate::VideoPlayer] handles the
+    /// bus. The seek is sent from another thread, the one posting the message may hold the
+    /// stream lock the seek waits for
+    fn watch_loop_end(&self) {
+        let playbin = self.source.downgrade();
+        let state = Arc::downgrade(&self.state);
+        self.events.watch(&self.bus, move |message| {
+            // the end is reached without a segment seek after a step, it has to be flushed
+            let flags = match message.view() {
+                gst::MessageView::SegmentDone(_) => gst::SeekFlags::ACCURATE,
+                gst::MessageView::Eos(_) => gst::SeekFlags::FLUSH | gst::SeekFlags::ACCURATE,
+                _ => return,
+            };
+            let (Some(playbin), Some(state)) = (playbin.upgrade(), state.upgrade()) else {
+                return;
+            };
+            playbin.call_async(move |playbin| {
+                let mut state = state.write().unwrap();
+                if !state.looping {
+                    return;
+                }
+                // without flushing, the next loop follows the last frame seamlessly
+                let seek = seek_event(&state, flags, loop_start(&state));
+                if flags.contains(gst::SeekFlags::FLUSH) {
+                    state.seek_seqnum = Some(seek.seqnum());
+                }
+                playbin.send_event(seek);
+            });
+        });
+    }
+
+    pub(crate) fn new_url_and_record<P: AsRef<Path>>(
+        url: &url::Url,
+        islive: bool,
+        file: P,
+        options: &RecordingOptions,
+    ) -> Result<Self, IcedGStreamerError> {
+        let video = Self::new_url(url, islive)?;
+        video.start_recording(file, options)?;
+        Ok(video)
     }
 
     /// get the volume of the video
@@ -245,6 +689,45 @@ impl GVideoUrl {
         self.source.set_property("volume", volume);
     }
 
+    /// Play the sound on another output while the video keeps playing, see
+    /// [crate::audio_outputs] for the devices
+    pub fn set_audio_sink(&self, sink: AudioSink) -> Result<(), IcedGStreamerError> {
+        let audio_sink = self
+            .source
+            .property::<Option<gst::Element>>("audio-sink")
+            .and_then(|sink| sink.downcast::<gst::Bin>().ok())
+            .ok_or(IcedGStreamerError::Cast)?;
+        crate::audio_output::replace_audio_output(&audio_sink, &sink)
+    }
+
+    /// the volume on the cubic scale, which follows the loudness that is heard, for sliders
+    pub fn volume_cubic(&self) -> f64 {
+        gst_audio::StreamVolume::convert_volume(
+            gst_audio::StreamVolumeFormat::Linear,
+            gst_audio::StreamVolumeFormat::Cubic,
+            self.source.property("volume"),
+        )
+    }
+
+    /// Set the volume on the cubic scale, from 0 to 1, so a slider feels even
+    pub fn set_volume_cubic(&self, volume: f64) {
+        self.set_volume(gst_audio::StreamVolume::convert_volume(
+            gst_audio::StreamVolumeFormat::Cubic,
+            gst_audio::StreamVolumeFormat::Linear,
+            volume.clamp(0., 1.),
+        ));
+    }
+
+    /// Mute the sound, the volume is kept
+    pub fn set_muted(&self, muted: bool) {
+        self.source.set_property("mute", muted);
+    }
+
+    /// if the sound is muted, with [GVideoUrl::set_muted] or by the sound server
+    pub fn is_muted(&self) -> bool {
+        self.source.property("mute")
+    }
+
     /// get the duration, if is live or pipewire, it is 0
     pub fn duration(&self) -> std::time::Duration {
         let state = self.state.read().unwrap();
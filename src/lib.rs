@@ -1,13 +1,21 @@
+mod audio_output;
+mod events;
+mod gstreamer_appsrc;
+mod gstreamer_launch;
 mod gstreamer_pipewire;
 mod gstreamer_playbin;
 mod id;
 mod pipeline;
+mod playlist;
+mod recording;
+mod subtitles;
 mod video_player;
 
 use gst::GenericFormattedValue;
 use gst::glib;
 use gst::prelude::*;
 use gstreamer as gst;
+use gstreamer_video as gst_video;
 use std::hash::Hash;
 use std::os::fd::RawFd;
 use std::path::Path;
@@ -23,18 +31,219 @@ pub use video_player::{Catalog, VideoPlayer, primary, video_background_primary};
 
 pub use gst::State as PlayingState;
 
+/// The pixel formats which can be rendered by [VideoPlayer] without a conversion on the cpu
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PixelFormat {
+    /// a full size Y plane followed by a half size interleaved UV plane
+    Nv12,
+    /// three planes, a full size Y plane, a half size U plane and a half size V plane
+    I420,
+    /// 4 bytes per pixel
+    Rgba,
+    /// 4 bytes per pixel
+    Bgra,
+    /// 4 bytes per pixel, the last byte is ignored
+    Rgbx,
+    /// 4 bytes per pixel, the last byte is ignored
+    Bgrx,
+    /// like [PixelFormat::Nv12], but every sample is 16 bits little endian, with 10 bits used
+    P010,
+}
+
+impl PixelFormat {
+    /// All the formats, sorted by preference
+    pub const ALL: [PixelFormat; 7] = [
+        Self::Nv12,
+        Self::I420,
+        Self::Rgba,
+        Self::Bgra,
+        Self::Rgbx,
+        Self::Bgrx,
+        Self::P010,
+    ];
+
+    /// the name of the format in gstreamer caps
+    pub fn as_gst_str(&self) -> &'static str {
+        match self {
+            Self::Nv12 => "NV12",
+            Self::I420 => "I420",
+            Self::Rgba => "RGBA",
+            Self::Bgra => "BGRA",
+            Self::Rgbx => "RGBx",
+            Self::Bgrx => "BGRx",
+            Self::P010 => "P010_10LE",
+        }
+    }
+
+    /// parse the format from the name in gstreamer caps
+    pub fn from_gst_str(format: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|pixel_format| pixel_format.as_gst_str() == format)
+    }
+
+    /// how many planes the format has
+    pub fn n_planes(&self) -> usize {
+        match self {
+            Self::Nv12 | Self::P010 => 2,
+            Self::I420 => 3,
+            Self::Rgba | Self::Bgra | Self::Rgbx | Self::Bgrx => 1,
+        }
+    }
+
+    /// the size in pixels of the plane, chroma planes are subsampled
+    pub fn plane_size(&self, plane: usize, width: u32, height: u32) -> (u32, u32) {
+        if plane == 0 {
+            (width, height)
+        } else {
+            (width.div_ceil(2), height.div_ceil(2))
+        }
+    }
+
+    /// how many bytes a pixel of the plane takes
+    pub fn plane_pixel_stride(&self, plane: usize) -> u32 {
+        match (self, plane) {
+            (Self::Nv12, 0) | (Self::I420, _) => 1,
+            (Self::Nv12, _) | (Self::P010, 0) => 2,
+            (Self::P010, _) | (Self::Rgba | Self::Bgra | Self::Rgbx | Self::Bgrx, _) => 4,
+        }
+    }
+}
+
+/// The matrix used to turn yuv to rgb
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ColorMatrix {
+    /// SD video
+    Bt601,
+    /// HD video
+    #[default]
+    Bt709,
+    /// UHD and HDR video
+    Bt2020,
+    Smpte240m,
+    Fcc,
+}
+
+/// The range of the yuv samples
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ColorRange {
+    /// 16-235 for luma, 16-240 for chroma, used by most videos
+    #[default]
+    Limited,
+    /// 0-255, used by jpeg and many webcams
+    Full,
+}
+
+/// The transfer function of the samples
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum TransferFunction {
+    /// the gamma of SDR video, it is shown as is
+    #[default]
+    Sdr,
+    /// SMPTE ST 2084, used by HDR10, it is tone mapped to SDR
+    Pq,
+    /// ARIB STD-B67, it is tone mapped to SDR
+    Hlg,
+}
+
+/// The colorimetry of [FrameData], read from the caps
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Colorimetry {
+    pub matrix: ColorMatrix,
+    pub range: ColorRange,
+    pub transfer: TransferFunction,
+}
+
+impl Colorimetry {
+    /// the unknown values of the caps fall back to BT.601 for SD video and BT.709 for others
+    fn from_info(info: &gst_video::VideoInfo) -> Self {
+        let colorimetry = info.colorimetry();
+        let matrix = match colorimetry.matrix() {
+            gst_video::VideoColorMatrix::Bt601 => ColorMatrix::Bt601,
+            gst_video::VideoColorMatrix::Bt709 => ColorMatrix::Bt709,
+            gst_video::VideoColorMatrix::Bt2020 => ColorMatrix::Bt2020,
+            gst_video::VideoColorMatrix::Smpte240m => ColorMatrix::Smpte240m,
+            gst_video::VideoColorMatrix::Fcc => ColorMatrix::Fcc,
+            _ if info.height() < 720 => ColorMatrix::Bt601,
+            _ => ColorMatrix::Bt709,
+        };
+        let range = match colorimetry.range() {
+            gst_video::VideoColorRange::Range0_255 => ColorRange::Full,
+            _ => ColorRange::Limited,
+        };
+        let transfer = match colorimetry.transfer() {
+            gst_video::VideoTransferFunction::Smpte2084 => TransferFunction::Pq,
+            gst_video::VideoTransferFunction::AribStdB67 => TransferFunction::Hlg,
+            _ => TransferFunction::Sdr,
+        };
+        Self {
+            matrix,
+            range,
+            transfer,
+        }
+    }
+}
+
+/// The layout of a plane inside [FrameData::pixels]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Plane {
+    /// where the plane starts
+    pub offset: usize,
+    /// how many bytes a row takes, including the padding
+    pub stride: u32,
+}
+
 /// the data of the frame
-/// NOTE: it is NV12, so not rgba
+/// The pixels are stored as [FrameData::format], every plane is described in
+/// [FrameData::planes], and the yuv formats are turned to rgb with [FrameData::colorimetry]
 #[derive(Debug, Clone)]
 pub struct FrameData {
     pub pixels: Vec<u8>,
     pub width: u32,
     pub height: u32,
+    pub format: PixelFormat,
+    pub planes: Vec<Plane>,
+    pub colorimetry: Colorimetry,
 }
 
 impl FrameData {
-    fn stride(&self) -> u32 {
-        self.width
+    /// read the frame from the sample pulled from appsink
+    /// The layout of the planes comes from the [gst_video::VideoMeta] of the buffer if the
+    /// decoder attached one, otherwise from the [gst_video::VideoInfo] of the caps
+    fn from_sample(sample: &gst::Sample) -> Result<Self, gst::FlowError> {
+        let buffer = sample.buffer().ok_or(gst::FlowError::Error)?;
+        let map = buffer.map_readable().map_err(|_| gst::FlowError::Error)?;
+
+        let caps = sample.caps().ok_or(gst::FlowError::Error)?;
+        let info =
+            gst_video::VideoInfo::from_caps(caps).map_err(|_| gst::FlowError::NotNegotiated)?;
+        let format = PixelFormat::from_gst_str(info.format().to_str())
+            .ok_or(gst::FlowError::NotNegotiated)?;
+
+        let (offsets, strides) = match buffer.meta::<gst_video::VideoMeta>() {
+            Some(meta) => (meta.offset().to_vec(), meta.stride().to_vec()),
+            None => (info.offset().to_vec(), info.stride().to_vec()),
+        };
+        let planes = offsets
+            .into_iter()
+            .zip(strides)
+            .take(format.n_planes())
+            .map(|(offset, stride)| {
+                Ok(Plane {
+                    offset,
+                    stride: u32::try_from(stride).map_err(|_| gst::FlowError::NotSupported)?,
+                })
+            })
+            .collect::<Result<Vec<_>, gst::FlowError>>()?;
+
+        Ok(Self {
+            pixels: map.as_slice().to_owned(),
+            width: info.width(),
+            height: info.height(),
+            format,
+            planes,
+            colorimetry: Colorimetry::from_info(&info),
+        })
     }
     fn data(&self) -> &[u8] {
         &self.pixels
@@ -44,20 +253,87 @@ impl FrameData {
     }
 }
 
-pub use gstreamer_playbin::GVideoUrl;
+/// the caps of all the appsinks, every [PixelFormat] is accepted so the decoded frames can skip
+/// the videoconvert
+fn app_sink_caps() -> gst::Caps {
+    gst::Caps::builder("video/x-raw")
+        .field(
+            "format",
+            gst::List::new(PixelFormat::ALL.map(|format| format.as_gst_str())),
+        )
+        .field("pixel-aspect-ratio", gst::Fraction::new(1, 1))
+        .build()
+}
+
+pub use gstreamer_playbin::{AudioTrack, GVideoUrl};
 
 pub use gstreamer_pipewire::GVideoPipewire;
 
+pub use gstreamer_launch::GVideoPipeline;
+
+pub use gstreamer_appsrc::{AppSrcFormat, AppSrcHandle, GVideoAppSrc};
+
+pub use events::GVideoEvent;
+
+pub use audio_output::{
+    AudioOutput, AudioOutputEvent, AudioSink, audio_output_subscription, audio_outputs,
+};
+
+pub use playlist::{Playlist, RepeatMode};
+
+pub use subtitles::{SubtitleCue, SubtitleTrack, parse_subtitles};
+
+pub use recording::{
+    AudioCodec, Container, EncoderPreset, Mp4Mode, RateControl, RecordingOptions, RecordingState,
+    VideoCodec,
+};
+
 #[derive(Debug, Default)]
 struct State {
     pub duration: std::time::Duration,
     pub position: std::time::Duration,
     pub volume: f64,
+    /// the duration should be queried, it is set again by the DurationChanged and AsyncDone
+    /// messages
     pub get_duration_attempt: bool,
+    /// when the duration and the position are queried the last time
+    pub last_query: Option<std::time::Instant>,
+    /// the seqnum of the last seek, to find the AsyncDone message which ends it
+    pub seek_seqnum: Option<gst::Seqnum>,
+    /// the playback rate, negative when playing backward
+    pub rate: f64,
+    /// a step is sent to the video sink, and its StepDone is not received yet
+    pub step_pending: bool,
+    /// the video starts again at the end, with segment seeks
+    pub looping: bool,
+    /// the part of the video which is looped, the whole video if None
+    pub loop_range: Option<(std::time::Duration, std::time::Duration)>,
+    /// the next uri is taken from it when the current one is about to finish
+    pub playlist: Arc<Mutex<Playlist>>,
+    /// the streams of the media and the ones which are played, only posted by playbin3
+    pub streams: Arc<Mutex<gstreamer_playbin::Streams>>,
+    /// the last subtitle received by the text sink
+    pub subtitle: Arc<Mutex<Option<SubtitleCue>>>,
+    /// the cues of the external SRT or WebVTT file, they replace the subtitles of the media
+    pub external_subtitles: Vec<SubtitleCue>,
 }
 impl State {
     fn new() -> Self {
-        Self::default()
+        Self {
+            rate: 1.,
+            ..Self::default()
+        }
+    }
+    /// the start and the end of the loop, None if it is not looping
+    fn loop_bounds(&self) -> Option<(gst::ClockTime, Option<gst::ClockTime>)> {
+        if !self.looping {
+            return None;
+        }
+        let time = |t: std::time::Duration| gst::ClockTime::from_nseconds(t.as_nanos() as _);
+        Some(match self.loop_range {
+            Some((start, stop)) => (time(start), Some(time(stop))),
+            None => (gst::ClockTime::ZERO, None),
+        })
     }
     fn with_try_get_duration(self, info_get_started: bool) -> Self {
         Self {
@@ -67,12 +343,14 @@ impl State {
     }
 }
 /// The container for the gstreamer
-/// Current it supports UrlPlayer and Pipewire
+/// Current it supports UrlPlayer, Pipewire, custom gst-launch Pipeline and AppSrc
 /// And also a empty [GVideo::None]
 #[derive(Debug)]
 pub enum GVideo {
     UrlPlayer(GVideoUrl),
     PipeWire(GVideoPipewire),
+    Pipeline(GVideoPipeline),
+    AppSrc(GVideoAppSrc),
     None,
 }
 
@@ -91,6 +369,7 @@ mod seal {
         url: url::Url,
         is_live: bool,
         file: Option<PathBuf>,
+        options: RecordingOptions,
     }
 
     impl<'a> UrlBinBuilderRef<'a> {
@@ -100,6 +379,7 @@ mod seal {
                 url,
                 is_live,
                 file: None,
+                options: RecordingOptions::default(),
             }
         }
         /// save it to a file
@@ -113,6 +393,11 @@ mod seal {
             self.file = file.map(|f| f.as_ref().to_path_buf());
             self
         }
+        /// set the [RecordingOptions] used by the saved file
+        pub fn recording_options(mut self, options: RecordingOptions) -> Self {
+            self.options = options;
+            self
+        }
 
         /// finish the modification
         pub fn finish(self) -> Result<(), IcedGStreamerError> {
@@ -121,6 +406,7 @@ mod seal {
                     &self.url,
                     self.is_live,
                     file,
+                    &self.options,
                 )?),
                 None => GVideo::UrlPlayer(GVideoUrl::new_url(&self.url, self.is_live)?),
             };
@@ -134,6 +420,7 @@ mod seal {
         path: u32,
         fd: RawFd,
         file: Option<PathBuf>,
+        options: RecordingOptions,
     }
 
     impl<'a> PipeWireBuilderRef<'a> {
@@ -143,6 +430,7 @@ mod seal {
                 path,
                 fd,
                 file: None,
+                options: RecordingOptions::default(),
             }
         }
         /// save it to a file
@@ -155,11 +443,19 @@ mod seal {
             self.file = file.map(|f| f.as_ref().to_path_buf());
             self
         }
+        /// set the [RecordingOptions] used by the saved file
+        pub fn recording_options(mut self, options: RecordingOptions) -> Self {
+            self.options = options;
+            self
+        }
         /// finish the modification
         pub fn finish(self) -> Result<(), IcedGStreamerError> {
             *self.video = match self.file {
                 Some(file) => GVideo::PipeWire(GVideoPipewire::new_pipewire_and_record(
-                    self.path, self.fd, file,
+                    self.path,
+                    self.fd,
+                    file,
+                    &self.options,
                 )?),
                 None => GVideo::PipeWire(GVideoPipewire::new_pipewire(self.path, self.fd)?),
             };
@@ -174,6 +470,7 @@ mod seal {
         path: u32,
         fd: RawFd,
         file: Option<PathBuf>,
+        options: RecordingOptions,
     }
 
     impl PipeWireBuilder {
@@ -183,6 +480,7 @@ mod seal {
                 path,
                 fd,
                 file: None,
+                options: RecordingOptions::default(),
             }
         }
         /// save it to a file
@@ -195,11 +493,19 @@ mod seal {
             self.file = file.map(|f| f.as_ref().to_path_buf());
             self
         }
+        /// set the [RecordingOptions] used by the saved file
+        pub fn recording_options(mut self, options: RecordingOptions) -> Self {
+            self.options = options;
+            self
+        }
         /// build a [GVideo]
         pub fn build(mut self) -> Result<GVideo, IcedGStreamerError> {
             self.video = match self.file {
                 Some(file) => GVideo::PipeWire(GVideoPipewire::new_pipewire_and_record(
-                    self.path, self.fd, file,
+                    self.path,
+                    self.fd,
+                    file,
+                    &self.options,
                 )?),
                 None => GVideo::PipeWire(GVideoPipewire::new_pipewire(self.path, self.fd)?),
             };
@@ -214,6 +520,7 @@ mod seal {
         url: url::Url,
         is_live: bool,
         file: Option<PathBuf>,
+        options: RecordingOptions,
     }
 
     impl UrlBinBuilder {
@@ -223,6 +530,7 @@ mod seal {
                 url,
                 is_live,
                 file: None,
+                options: RecordingOptions::default(),
             }
         }
 
@@ -237,6 +545,11 @@ mod seal {
             self.file = file.map(|f| f.as_ref().to_path_buf());
             self
         }
+        /// set the [RecordingOptions] used by the saved file
+        pub fn recording_options(mut self, options: RecordingOptions) -> Self {
+            self.options = options;
+            self
+        }
         /// build a [GVideo]
         pub fn build(mut self) -> Result<GVideo, IcedGStreamerError> {
             self.video = match self.file {
@@ -244,12 +557,138 @@ mod seal {
                     &self.url,
                     self.is_live,
                     file,
+                    &self.options,
                 )?),
                 None => GVideo::UrlPlayer(GVideoUrl::new_url(&self.url, self.is_live)?),
             };
             Ok(self.video)
         }
     }
+
+    /// The builder to modify a [GVideo], whose inner will be [GVideoPipeline]
+    #[derive(Debug)]
+    pub struct PipelineBuilderRef<'a> {
+        video: &'a mut GVideo,
+        description: String,
+    }
+
+    impl<'a> PipelineBuilderRef<'a> {
+        pub(crate) fn new(video: &'a mut GVideo, description: String) -> Self {
+            Self { video, description }
+        }
+        /// finish the modification
+        pub fn finish(self) -> Result<(), IcedGStreamerError> {
+            *self.video = GVideo::Pipeline(GVideoPipeline::new_pipeline(&self.description)?);
+            Ok(())
+        }
+    }
+
+    /// The builder to build a [GVideo], whose inner is [GVideoPipeline]
+    #[derive(Debug)]
+    pub struct PipelineBuilder {
+        video: GVideo,
+        description: String,
+    }
+
+    impl PipelineBuilder {
+        pub(crate) fn new(description: String) -> Self {
+            Self {
+                video: GVideo::None,
+                description,
+            }
+        }
+        /// build a [GVideo]
+        pub fn build(mut self) -> Result<GVideo, IcedGStreamerError> {
+            self.video = GVideo::Pipeline(GVideoPipeline::new_pipeline(&self.description)?);
+            Ok(self.video)
+        }
+    }
+
+    /// The builder to modify a [GVideo], whose inner will be [GVideoAppSrc]
+    #[derive(Debug)]
+    pub struct AppSrcBuilderRef<'a> {
+        video: &'a mut GVideo,
+        file: Option<PathBuf>,
+        options: RecordingOptions,
+    }
+
+    impl<'a> AppSrcBuilderRef<'a> {
+        pub(crate) fn new(video: &'a mut GVideo) -> Self {
+            Self {
+                video,
+                file: None,
+                options: RecordingOptions::default(),
+            }
+        }
+        /// save it to a file
+        pub fn save_file<P: AsRef<Path>>(mut self, file: P) -> Self {
+            self.file = Some(file.as_ref().to_path_buf());
+            self
+        }
+        /// maybe save it to a file
+        pub fn save_file_maybe<P: AsRef<Path>>(mut self, file: Option<P>) -> Self {
+            self.file = file.map(|f| f.as_ref().to_path_buf());
+            self
+        }
+        /// set the [RecordingOptions] used by the saved file
+        pub fn recording_options(mut self, options: RecordingOptions) -> Self {
+            self.options = options;
+            self
+        }
+        /// finish the modification
+        pub fn finish(self) -> Result<(), IcedGStreamerError> {
+            *self.video = match self.file {
+                Some(file) => {
+                    GVideo::AppSrc(GVideoAppSrc::new_appsrc_and_record(file, &self.options)?)
+                }
+                None => GVideo::AppSrc(GVideoAppSrc::new_appsrc()?),
+            };
+            Ok(())
+        }
+    }
+
+    /// The builder to build a [GVideo], whose inner is [GVideoAppSrc]
+    #[derive(Debug)]
+    pub struct AppSrcBuilder {
+        video: GVideo,
+        file: Option<PathBuf>,
+        options: RecordingOptions,
+    }
+
+    impl AppSrcBuilder {
+        pub(crate) fn new() -> Self {
+            Self {
+                video: GVideo::None,
+                file: None,
+                options: RecordingOptions::default(),
+            }
+        }
+        /// save it to a file
+        pub fn save_file<P: AsRef<Path>>(mut self, file: P) -> Self {
+            self.file = Some(file.as_ref().to_path_buf());
+            self
+        }
+        /// maybe save it to a file
+        pub fn save_file_maybe<P: AsRef<Path>>(mut self, file: Option<P>) -> Self {
+            self.file = file.map(|f| f.as_ref().to_path_buf());
+            self
+        }
+        /// set the [RecordingOptions] used by the saved file
+        pub fn recording_options(mut self, options: RecordingOptions) -> Self {
+            self.options = options;
+            self
+        }
+        /// build a [GVideo]
+        pub fn build(mut self) -> Result<GVideo, IcedGStreamerError> {
+            self.video = match self.file {
+                Some(file) => {
+                    GVideo::AppSrc(GVideoAppSrc::new_appsrc_and_record(file, &self.options)?)
+                }
+                None => GVideo::AppSrc(GVideoAppSrc::new_appsrc()?),
+            };
+            Ok(self.video)
+        }
+    }
 }
 use seal::*;
 
@@ -269,6 +708,19 @@ impl GVideo {
         UrlBinBuilderRef::new(self, url, is_live)
     }
 
+    /// create a new [PipelineBuilderRef], this is used to rebuild a [GVideo]
+    pub fn open_pipeline<'a>(
+        &'a mut self,
+        description: impl Into<String>,
+    ) -> PipelineBuilderRef<'a> {
+        PipelineBuilderRef::new(self, description.into())
+    }
+
+    /// create a new [AppSrcBuilderRef], this is used to rebuild a [GVideo]
+    pub fn open_appsrc<'a>(&'a mut self) -> AppSrcBuilderRef<'a> {
+        AppSrcBuilderRef::new(self)
+    }
+
     /// create a new [PipeWireBuilder], this is used to build a [GVideo]
     pub fn new_pipewire(path: u32, fd: RawFd) -> PipeWireBuilder {
         PipeWireBuilder::new(path, fd)
@@ -279,6 +731,18 @@ impl GVideo {
         UrlBinBuilder::new(url, is_live)
     }
 
+    /// create a new [PipelineBuilder] from a gst-launch description, like
+    /// `videotestsrc ! video/x-raw,width=640,height=480`, the appsink is appended automatically
+    pub fn new_pipeline(description: impl Into<String>) -> PipelineBuilder {
+        PipelineBuilder::new(description.into())
+    }
+
+    /// create a new [AppSrcBuilder], the frames are pushed with the [AppSrcHandle] from
+    /// [GVideoAppSrc::handle]
+    pub fn new_appsrc() -> AppSrcBuilder {
+        AppSrcBuilder::new()
+    }
+
     /// cast the file from [GVideo] to [GVideoUrl], if not match, then panic
     pub fn as_url(&self) -> &GVideoUrl {
         let Self::UrlPlayer(url_player) = &self else {
@@ -293,6 +757,20 @@ impl GVideo {
         };
         pw_instance
     }
+    /// cast the file from [GVideo] to [GVideoPipeline], if not match, then panic
+    pub fn as_pipeline(&self) -> &GVideoPipeline {
+        let Self::Pipeline(pipeline) = self else {
+            panic!("Not this type");
+        };
+        pipeline
+    }
+    /// cast the file from [GVideo] to [GVideoAppSrc], if not match, then panic
+    pub fn as_appsrc(&self) -> &GVideoAppSrc {
+        let Self::AppSrc(appsrc) = self else {
+            panic!("Not this type");
+        };
+        appsrc
+    }
     fn is_none(&self) -> bool {
         matches!(self, Self::None)
     }
@@ -301,6 +779,8 @@ impl GVideo {
             Self::None => StreamType::Empty,
             Self::PipeWire(_) => StreamType::PipeWire,
             Self::UrlPlayer(_) => StreamType::UrlPlayer,
+            Self::Pipeline(_) => StreamType::Pipeline,
+            Self::AppSrc(_) => StreamType::AppSrc,
         }
     }
     fn frame_data(&self) -> Option<FrameData> {
@@ -308,6 +788,8 @@ impl GVideo {
             Self::None => None,
             Self::UrlPlayer(player) => player.frame_data(),
             Self::PipeWire(pipewire) => pipewire.frame_data(),
+            Self::Pipeline(pipeline) => pipeline.frame_data(),
+            Self::AppSrc(appsrc) => appsrc.frame_data(),
         }
     }
 
@@ -316,6 +798,8 @@ impl GVideo {
             Self::None => None,
             Self::UrlPlayer(player) => Some(player.upload_frame.clone()),
             Self::PipeWire(pipewire) => Some(pipewire.upload_frame.clone()),
+            Self::Pipeline(pipeline) => Some(pipeline.upload_frame.clone()),
+            Self::AppSrc(appsrc) => Some(appsrc.upload_frame.clone()),
         }
     }
     fn alive(&self) -> Option<Arc<AtomicBool>> {
@@ -323,6 +807,8 @@ impl GVideo {
             Self::None => None,
             Self::UrlPlayer(player) => Some(player.alive.clone()),
             Self::PipeWire(pipewire) => Some(pipewire.alive.clone()),
+            Self::Pipeline(pipeline) => Some(pipeline.alive.clone()),
+            Self::AppSrc(appsrc) => Some(appsrc.alive.clone()),
         }
     }
     fn id(&self) -> Option<id::Id> {
@@ -330,6 +816,8 @@ impl GVideo {
             Self::None => None,
             Self::UrlPlayer(player) => Some(player.id),
             Self::PipeWire(pipewire) => Some(pipewire.id),
+            Self::Pipeline(pipeline) => Some(pipeline.id),
+            Self::AppSrc(appsrc) => Some(appsrc.id),
         }
     }
     fn frame(&self) -> Option<Arc<Mutex<Option<FrameData>>>> {
@@ -337,6 +825,8 @@ impl GVideo {
             Self::None => None,
             Self::UrlPlayer(player) => Some(player.frame.clone()),
             Self::PipeWire(pipewire) => Some(pipewire.frame.clone()),
+            Self::Pipeline(pipeline) => Some(pipeline.frame.clone()),
+            Self::AppSrc(appsrc) => Some(appsrc.frame.clone()),
         }
     }
     fn state(&self) -> Option<Arc<RwLock<State>>> {
@@ -344,6 +834,8 @@ impl GVideo {
             Self::None => None,
             Self::UrlPlayer(player) => Some(player.state.clone()),
             Self::PipeWire(pipewire) => Some(pipewire.state.clone()),
+            Self::Pipeline(pipeline) => Some(pipeline.state.clone()),
+            Self::AppSrc(appsrc) => Some(appsrc.state.clone()),
         }
     }
     pub fn play_state(&self) -> gst::State {
@@ -351,6 +843,52 @@ impl GVideo {
             Self::None => gst::State::Null,
             Self::UrlPlayer(player) => player.play_state(),
             Self::PipeWire(pipewire) => pipewire.play_state(),
+            Self::Pipeline(pipeline) => pipeline.play_state(),
+            Self::AppSrc(appsrc) => appsrc.play_state(),
+        }
+    }
+    /// Stop the pipeline after it is drained, see [GVideoInner::shutdown]
+    pub async fn shutdown(self) -> Result<(), IcedGStreamerError> {
+        match self {
+            Self::None => Ok(()),
+            Self::UrlPlayer(player) => player.shutdown().await,
+            Self::PipeWire(pipewire) => pipewire.shutdown().await,
+            Self::Pipeline(pipeline) => pipeline.shutdown().await,
+            Self::AppSrc(appsrc) => appsrc.shutdown().await,
+        }
+    }
+
+    /// Stop the pipeline after it is drained or after the timeout, see
+    /// [GVideoInner::shutdown_blocking]
+    pub fn shutdown_blocking(self, timeout: std::time::Duration) -> Result<(), IcedGStreamerError> {
+        match self {
+            Self::None => Ok(()),
+            Self::UrlPlayer(player) => player.shutdown_blocking(timeout),
+            Self::PipeWire(pipewire) => pipewire.shutdown_blocking(timeout),
+            Self::Pipeline(pipeline) => pipeline.shutdown_blocking(timeout),
+            Self::AppSrc(appsrc) => appsrc.shutdown_blocking(timeout),
+        }
+    }
+
+    /// The messages of the bus, they are received even if the [VideoPlayer] is not shown
+    pub fn subscription(&self) -> iced_futures::Subscription<GVideoEvent> {
+        match self {
+            Self::None => iced_futures::Subscription::none(),
+            Self::UrlPlayer(player) => player.subscription(),
+            Self::PipeWire(pipewire) => pipewire.subscription(),
+            Self::Pipeline(pipeline) => pipeline.subscription(),
+            Self::AppSrc(appsrc) => appsrc.subscription(),
+        }
+    }
+
+    /// the [RecordingState] of the video, always stopped for [GVideo::None]
+    pub fn recording_state(&self) -> RecordingState {
+        match self {
+            Self::None => RecordingState::Stopped,
+            Self::UrlPlayer(player) => player.recording_state(),
+            Self::PipeWire(pipewire) => pipewire.recording_state(),
+            Self::Pipeline(pipeline) => pipeline.recording_state(),
+            Self::AppSrc(appsrc) => appsrc.recording_state(),
         }
     }
     fn source(&self) -> Option<&gst::Bin> {
@@ -358,6 +896,8 @@ impl GVideo {
             Self::None => None,
             Self::UrlPlayer(player) => Some(&player.source),
             Self::PipeWire(pipewire) => Some(&pipewire.source),
+            Self::Pipeline(pipeline) => Some(&pipeline.source),
+            Self::AppSrc(appsrc) => Some(&appsrc.source),
         }
     }
     fn bus(&self) -> Option<&gst::Bus> {
@@ -365,6 +905,8 @@ impl GVideo {
             Self::None => None,
             Self::UrlPlayer(player) => Some(&player.bus),
             Self::PipeWire(pipewire) => Some(&pipewire.bus),
+            Self::Pipeline(pipeline) => Some(&pipeline.bus),
+            Self::AppSrc(appsrc) => Some(&appsrc.bus),
         }
     }
     pub fn set_state(&self, state: gst::State) {
@@ -376,6 +918,12 @@ impl GVideo {
             Self::PipeWire(pipewire) => {
                 pipewire.set_state(state);
             }
+            Self::Pipeline(pipeline) => {
+                pipeline.set_state(state);
+            }
+            Self::AppSrc(appsrc) => {
+                appsrc.set_state(state);
+            }
         }
     }
     pub(crate) fn pending_events(&self) -> Vec<GsEvent> {
@@ -390,6 +938,14 @@ impl GVideo {
                 let mut pending_events = player.pending_events.write().unwrap();
                 std::mem::swap(&mut *pending_events, &mut events);
             }
+            Self::Pipeline(pipeline) => {
+                let mut pending_events = pipeline.pending_events.write().unwrap();
+                std::mem::swap(&mut *pending_events, &mut events);
+            }
+            Self::AppSrc(appsrc) => {
+                let mut pending_events = appsrc.pending_events.write().unwrap();
+                std::mem::swap(&mut *pending_events, &mut events);
+            }
         }
         events
     }
@@ -397,7 +953,12 @@ impl GVideo {
 
 #[derive(Debug)]
 pub(crate) enum GsEvent {
-    Jump(Position),
+    Jump(Position, SeekMode),
+    Rate(f64),
+    /// step some frames, backward if negative
+    Step(i64),
+    /// looping is enabled, disabled or its range is changed
+    Loop,
 }
 
 /// The main container for a gstreamer task
@@ -411,6 +972,8 @@ pub struct GVideoInner<const X: usize> {
     frame: Arc<Mutex<Option<FrameData>>>,
     id: id::Id,
     pending_events: RwLock<Vec<GsEvent>>,
+    recording: Mutex<Option<recording::Recording>>,
+    events: events::BusEvents,
 }
 
 #[derive(Debug, Error)]
@@ -439,6 +1002,26 @@ pub enum IcedGStreamerError {
     NoExtension,
     #[error("Unsupported extension")]
     UnsupportedExtension,
+    #[error("{0}")]
+    Flow(#[from] gst::FlowError),
+    #[error("the size of the frame does not match its format")]
+    FrameSize,
+    #[error("the gstreamer element {0} is not installed")]
+    MissingElement(&'static str),
+    #[error("{0:?} can not be stored in {1:?}")]
+    IncompatibleContainer(VideoCodec, Container),
+    #[error("{0:?} audio can not be stored in {1:?}")]
+    IncompatibleAudioContainer(AudioCodec, Container),
+    #[error("the pipeline is not drained before the timeout")]
+    ShutdownTimeout,
+    #[error("there is no audio track {0}")]
+    NoAudioTrack(usize),
+    #[error("there is no subtitle track {0}")]
+    NoSubtitleTrack(usize),
+    #[error("there is no audio output {0}")]
+    NoAudioDevice(String),
+    #[error("{0} is not a playback rate")]
+    InvalidRate(f64),
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -460,6 +1043,50 @@ impl From<Position> for GenericFormattedValue {
     }
 }
 
+impl Position {
+    /// the time of the position, a frame is converted with the framerate of the video
+    pub(crate) fn to_time(self, framerate: Option<gst::Fraction>) -> Option<gst::ClockTime> {
+        match self {
+            Position::Time(t) => Some(gst::ClockTime::from_nseconds(t.as_nanos() as _)),
+            Position::Frame(f) => {
+                let framerate = framerate.filter(|rate| rate.numer() > 0 && rate.denom() > 0)?;
+                let nanos = f as u128 * framerate.denom() as u128 * 1_000_000_000
+                    / framerate.numer() as u128;
+                Some(gst::ClockTime::from_nseconds(u64::try_from(nanos).ok()?))
+            }
+        }
+    }
+}
+
+/// Where a seek lands
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum SeekMode {
+    /// Exactly on the position, the frames before it are decoded from the previous keyframe
+    #[default]
+    Accurate,
+    /// On a keyframe near the position, fast but not exact
+    KeyUnit,
+    /// On the keyframe before the position
+    SnapBefore,
+    /// On the keyframe after the position
+    SnapAfter,
+    /// On the keyframe nearest to the position
+    Nearest,
+}
+
+impl SeekMode {
+    pub(crate) fn flags(self) -> gst::SeekFlags {
+        gst::SeekFlags::FLUSH
+            | match self {
+                SeekMode::Accurate => gst::SeekFlags::ACCURATE,
+                SeekMode::KeyUnit => gst::SeekFlags::KEY_UNIT,
+                SeekMode::SnapBefore => gst::SeekFlags::KEY_UNIT | gst::SeekFlags::SNAP_BEFORE,
+                SeekMode::SnapAfter => gst::SeekFlags::KEY_UNIT | gst::SeekFlags::SNAP_AFTER,
+                SeekMode::Nearest => gst::SeekFlags::KEY_UNIT | gst::SeekFlags::SNAP_NEAREST,
+            }
+    }
+}
+
 impl From<std::time::Duration> for Position {
     fn from(t: std::time::Duration) -> Self {
         Position::Time(t)
@@ -472,10 +1099,14 @@ impl From<u64> for Position {
     }
 }
 
+/// how long the drop waits for the pipeline to drain
+const DROP_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
 impl<const X: usize> Drop for GVideoInner<X> {
     fn drop(&mut self) {
-        self.source.send_event(gst::event::Eos::new());
-        self.alive.store(false, Ordering::SeqCst);
+        if let Err(err) = self.stop_pipeline(Some(DROP_TIMEOUT)) {
+            log::warn!("failed to stop the pipeline: {err}");
+        }
     }
 }
 
@@ -483,6 +1114,8 @@ impl<const X: usize> Drop for GVideoInner<X> {
 pub enum StreamType {
     UrlPlayer,
     PipeWire,
+    Pipeline,
+    AppSrc,
     Empty,
 }
 
@@ -506,6 +1139,8 @@ impl<const X: usize> GVideoInner<X> {
         match X {
             0 => StreamType::UrlPlayer,
             1 => StreamType::PipeWire,
+            2 => StreamType::Pipeline,
+            3 => StreamType::AppSrc,
             _ => unreachable!(),
         }
     }
@@ -517,4 +1152,159 @@ impl<const X: usize> GVideoInner<X> {
             _ => {}
         }
     }
+
+    /// Set the pipeline to Null, so the threads of gstreamer are stopped. If a file is written,
+    /// EOS is sent before and it waits until it goes through the pipeline, so the file is finished.
+    /// It waits until the pipeline is drained, use [GVideoInner::shutdown_blocking] if the
+    /// pipeline may never end.
+    pub async fn shutdown(self) -> Result<(), IcedGStreamerError> {
+        let (sender, receiver) = iced_futures::futures::channel::oneshot::channel();
+        std::thread::spawn(move || {
+            let _ = sender.send(self.stop_pipeline(None));
+        });
+        receiver.await.map_err(|_| IcedGStreamerError::Sync)?
+    }
+
+    /// Same as [GVideoInner::shutdown], but blocks the thread, and gives up waiting for the EOS
+    /// after the timeout. The pipeline is set to Null in both cases.
+    pub fn shutdown_blocking(self, timeout: std::time::Duration) -> Result<(), IcedGStreamerError> {
+        self.stop_pipeline(Some(timeout))
+    }
+
+    fn stop_pipeline(
+        &self,
+        timeout: Option<std::time::Duration>,
+    ) -> Result<(), IcedGStreamerError> {
+        let state = self.source.current_state();
+        if state == gst::State::Null {
+            self.alive.store(false, Ordering::SeqCst);
+            return Ok(());
+        }
+        // the muxers only finish the files at EOS, like the index of mp4. Without a file there is
+        // nothing to drain
+        // the EOS of the drain does not start the loop again
+        self.state.write().unwrap().looping = false;
+        let result = if self.is_writing_file() {
+            if state == gst::State::Paused {
+                // the sinks do not take the EOS while paused
+                self.source.set_state(gst::State::Playing)?;
+            }
+            // the EOS and the errors already on the bus are not the ones of this drain
+            self.bus.set_flushing(true);
+            self.bus.set_flushing(false);
+            let eos = gst::event::Eos::new();
+            let seqnum = eos.seqnum();
+            self.source.send_event(eos);
+            let deadline = timeout.map(|timeout| std::time::Instant::now() + timeout);
+            loop {
+                let timeout = deadline.map(|deadline| {
+                    let left = deadline.saturating_duration_since(std::time::Instant::now());
+                    gst::ClockTime::from_nseconds(left.as_nanos() as u64)
+                });
+                let Some(msg) = self
+                    .bus
+                    .timed_pop_filtered(timeout, &[gst::MessageType::Eos, gst::MessageType::Error])
+                else {
+                    break Err(IcedGStreamerError::ShutdownTimeout);
+                };
+                match msg.view() {
+                    gst::MessageView::Error(err) => {
+                        break Err(IcedGStreamerError::Glib(err.error()));
+                    }
+                    _ if msg.seqnum() == seqnum => break Ok(()),
+                    _ => {}
+                }
+            }
+        } else {
+            Ok(())
+        };
+        self.source.set_state(gst::State::Null)?;
+        self.alive.store(false, Ordering::SeqCst);
+        result
+    }
+
+    /// if a filesink is in the pipeline
+    fn is_writing_file(&self) -> bool {
+        self.source
+            .iterate_recurse()
+            .into_iter()
+            .filter_map(Result::ok)
+            .any(|element| {
+                element
+                    .factory()
+                    .is_some_and(|factory| factory.name() == "filesink")
+            })
+    }
+
+    /// The messages of the bus, they are received even if the [VideoPlayer] is not shown
+    pub fn subscription(&self) -> iced_futures::Subscription<GVideoEvent> {
+        self.events
+            .subscription(self.id, &self.bus, &self.source, &self.state)
+    }
+
+    /// Start recording to a file, the current recording is finished before. The recording is
+    /// attached to the tee of the video, and to the one of the sound of a [GVideoUrl]. A custom
+    /// pipeline can be recorded if it has a tee named `tee`
+    pub fn start_recording<P: AsRef<Path>>(
+        &self,
+        file: P,
+        options: &RecordingOptions,
+    ) -> Result<(), IcedGStreamerError> {
+        let (video_bin, audio_bin) = match self.stream_type() {
+            StreamType::UrlPlayer => {
+                let sink = |name| {
+                    self.source
+                        .property::<Option<gst::Element>>(name)
+                        .and_then(|sink| sink.downcast::<gst::Bin>().ok())
+                };
+                (
+                    sink("video-sink").ok_or(IcedGStreamerError::Cast)?,
+                    sink("audio-sink"),
+                )
+            }
+            _ => (self.source.clone(), None),
+        };
+        let mut recording = self.recording.lock().unwrap();
+        if let Some(recording) = recording.take() {
+            recording.stop();
+        }
+        *recording = Some(recording::Recording::start(
+            &video_bin,
+            audio_bin.as_ref(),
+            file.as_ref(),
+            options,
+        )?);
+        Ok(())
+    }
+
+    /// Stop recording the file, the preview keeps running
+    pub fn stop_recording(&self) {
+        if let Some(recording) = self.recording.lock().unwrap().take() {
+            recording.stop();
+        }
+    }
+
+    /// the state of the recording started with [GVideoInner::start_recording]
+    pub fn recording_state(&self) -> RecordingState {
+        self.recording
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map_or(RecordingState::Stopped, |recording| recording.state())
+    }
+
+    /// Drop the frames of the recording until it is resumed, the preview keeps running
+    /// The paused time is removed from the file, so it has no gap
+    pub fn pause_recording(&self) {
+        if let Some(recording) = self.recording.lock().unwrap().as_ref() {
+            recording.set_paused(true);
+        }
+    }
+
+    /// Continue a paused recording
+    pub fn resume_recording(&self) {
+        if let Some(recording) = self.recording.lock().unwrap().as_ref() {
+            recording.set_paused(false);
+        }
+    }
 }
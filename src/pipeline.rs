@@ -1,4 +1,6 @@
-use crate::FrameData;
+use crate::{
+    ColorMatrix, ColorRange, Colorimetry, FrameData, PixelFormat, Plane, TransferFunction,
+};
 use iced_wgpu::primitive::{Pipeline, Primitive};
 use iced_wgpu::wgpu;
 use std::{
@@ -13,13 +15,110 @@ use std::{
 #[repr(C)]
 struct Uniforms {
     rect: [f32; 4],
+    yuv_offset: [f32; 4],
+    yuv_scale: [f32; 4],
+    // the rows of the matrix
+    yuv2rgb: [[f32; 4]; 3],
+    format: u32,
+    transfer: u32,
     // because wgpu min_uniform_buffer_offset_alignment
-    _pad: [u8; 240],
+    _pad: [u8; 152],
+}
+
+/// The conversion from the yuv samples to rgb for a [Colorimetry]
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct YuvConversion {
+    offset: [f32; 4],
+    scale: [f32; 4],
+    yuv2rgb: [[f32; 4]; 3],
+}
+
+impl YuvConversion {
+    fn new(colorimetry: Colorimetry) -> Self {
+        let (kr, kb) = match colorimetry.matrix {
+            ColorMatrix::Bt601 => (0.299, 0.114),
+            ColorMatrix::Bt709 => (0.2126, 0.0722),
+            ColorMatrix::Bt2020 => (0.2627, 0.0593),
+            ColorMatrix::Smpte240m => (0.212, 0.087),
+            ColorMatrix::Fcc => (0.30, 0.11),
+        };
+        let kg = 1. - kr - kb;
+        let yuv2rgb = [
+            [1., 0., 2. * (1. - kr), 0.],
+            [1., -2. * kb * (1. - kb) / kg, -2. * kr * (1. - kr) / kg, 0.],
+            [1., 2. * (1. - kb), 0., 0.],
+        ];
+        let (offset, scale) = match colorimetry.range {
+            ColorRange::Limited => (
+                [16. / 255., 128. / 255., 128. / 255., 0.],
+                [219. / 255., 224. / 255., 224. / 255., 1.],
+            ),
+            ColorRange::Full => ([0., 128. / 255., 128. / 255., 0.], [1., 1., 1., 1.]),
+        };
+        Self {
+            offset,
+            scale,
+            yuv2rgb,
+        }
+    }
+}
+
+/// the path in `shader.wgsl` to apply the transfer function
+fn shader_transfer(transfer: TransferFunction) -> u32 {
+    match transfer {
+        TransferFunction::Sdr => 0,
+        TransferFunction::Pq => 1,
+        TransferFunction::Hlg => 2,
+    }
+}
+
+/// the path in `shader.wgsl` to turn the planes to rgb
+fn shader_format(format: PixelFormat) -> u32 {
+    match format {
+        PixelFormat::Nv12 => 0,
+        PixelFormat::I420 => 1,
+        PixelFormat::Rgba | PixelFormat::Bgra | PixelFormat::Rgbx | PixelFormat::Bgrx => 2,
+        PixelFormat::P010 => 3,
+    }
+}
+
+/// the texture format of the plane, the planes not used by the format are left as a 1x1 texture
+/// 16 bits samples are split into 8 bits channels, and combined in the shader, so every texture
+/// can be filtered
+fn plane_texture_format(format: PixelFormat, plane: usize) -> wgpu::TextureFormat {
+    match (format, plane) {
+        (PixelFormat::Rgba | PixelFormat::Rgbx, 0) => wgpu::TextureFormat::Rgba8Unorm,
+        (PixelFormat::Bgra | PixelFormat::Bgrx, 0) => wgpu::TextureFormat::Bgra8Unorm,
+        (PixelFormat::Nv12, 1) | (PixelFormat::P010, 0) => wgpu::TextureFormat::Rg8Unorm,
+        (PixelFormat::P010, 1) => wgpu::TextureFormat::Rgba8Unorm,
+        _ => wgpu::TextureFormat::R8Unorm,
+    }
+}
+
+/// the bytes of [FrameData::pixels] which are copied to the texture of the plane
+/// None if the stride is shorter than a row, or the plane is out of the frame
+fn plane_range(
+    format: PixelFormat,
+    plane: usize,
+    (width, height): (u32, u32),
+    layout: Plane,
+    len: usize,
+) -> Option<std::ops::Range<usize>> {
+    let (plane_width, plane_height) = format.plane_size(plane, width, height);
+    let row_size = plane_width * format.plane_pixel_stride(plane);
+    if layout.stride < row_size || plane_height == 0 {
+        return None;
+    }
+    let plane_len = layout.stride as usize * (plane_height as usize - 1) + row_size as usize;
+    let range = layout.offset..layout.offset + plane_len;
+    (range.end <= len).then_some(range)
 }
 
 struct VideoEntry {
-    texture_y: wgpu::Texture,
-    texture_uv: wgpu::Texture,
+    textures: [wgpu::Texture; 3],
+    format: PixelFormat,
+    size: (u32, u32),
+    colorimetry: Colorimetry,
     instances: wgpu::Buffer,
     bg0: wgpu::BindGroup,
     alive: Arc<AtomicBool>,
@@ -73,7 +172,7 @@ impl Pipeline for VideoPipeline {
                 },
                 wgpu::BindGroupLayoutEntry {
                     binding: 3,
-                    visibility: wgpu::ShaderStages::VERTEX,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
                     ty: wgpu::BindingType::Buffer {
                         ty: wgpu::BufferBindingType::Uniform,
                         has_dynamic_offset: true,
@@ -81,6 +180,16 @@ impl Pipeline for VideoPipeline {
                     },
                     count: None,
                 },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
             ],
         });
 
@@ -151,81 +260,74 @@ impl Pipeline for VideoPipeline {
             .collect();
         for id in ids {
             if let Some(video) = self.videos.remove(&id) {
-                video.texture_y.destroy();
-                video.texture_uv.destroy();
-                video.instances.destroy();
+                video.destroy();
             }
         }
     }
 }
 
+impl VideoEntry {
+    fn destroy(&self) {
+        for texture in &self.textures {
+            texture.destroy();
+        }
+        self.instances.destroy();
+    }
+}
+
 impl VideoPipeline {
-    #[allow(clippy::too_many_arguments)]
     fn upload(
         &mut self,
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         video_id: u64,
         alive: &Arc<AtomicBool>,
-        (width, height): (u32, u32),
-        frame: &[u8],
-        stride: Option<u32>,
+        frame: &FrameData,
     ) {
-        // Use stride from GStreamer's VideoMeta if available, otherwise assume stride == width
-        let stride = stride.unwrap_or(width);
+        let (width, height) = frame.size();
+        let format = frame.format;
+        // the texture is created again when the caps is renegotiated
+        if let Some(video) = self.videos.get(&video_id)
+            && (video.format != format || video.size != (width, height))
+        {
+            video.destroy();
+            self.videos.remove(&video_id);
+        }
         if let Entry::Vacant(entry) = self.videos.entry(video_id) {
-            let texture_y = device.create_texture(&wgpu::TextureDescriptor {
-                label: Some("iced_video_player texture"),
-                size: wgpu::Extent3d {
-                    width,
-                    height,
-                    depth_or_array_layers: 1,
-                },
-                mip_level_count: 1,
-                sample_count: 1,
-                dimension: wgpu::TextureDimension::D2,
-                format: wgpu::TextureFormat::R8Unorm,
-                usage: wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::TEXTURE_BINDING,
-                view_formats: &[],
-            });
-
-            let texture_uv = device.create_texture(&wgpu::TextureDescriptor {
-                label: Some("iced_video_player texture"),
-                size: wgpu::Extent3d {
-                    width: width / 2,
-                    height: height / 2,
-                    depth_or_array_layers: 1,
-                },
-                mip_level_count: 1,
-                sample_count: 1,
-                dimension: wgpu::TextureDimension::D2,
-                format: wgpu::TextureFormat::Rg8Unorm,
-                usage: wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::TEXTURE_BINDING,
-                view_formats: &[],
-            });
-
-            let view_y = texture_y.create_view(&wgpu::TextureViewDescriptor {
-                label: Some("iced_video_player texture view"),
-                format: None,
-                dimension: None,
-                aspect: wgpu::TextureAspect::All,
-                base_mip_level: 0,
-                mip_level_count: None,
-                base_array_layer: 0,
-                array_layer_count: None,
-                usage: None,
+            let textures = [0, 1, 2].map(|plane| {
+                let (width, height) = if plane < format.n_planes() {
+                    format.plane_size(plane, width, height)
+                } else {
+                    (1, 1)
+                };
+                device.create_texture(&wgpu::TextureDescriptor {
+                    label: Some("iced_video_player texture"),
+                    size: wgpu::Extent3d {
+                        width,
+                        height,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: plane_texture_format(format, plane),
+                    usage: wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::TEXTURE_BINDING,
+                    view_formats: &[],
+                })
             });
 
-            let view_uv = texture_uv.create_view(&wgpu::TextureViewDescriptor {
-                label: Some("iced_video_player texture view"),
-                format: None,
-                dimension: None,
-                aspect: wgpu::TextureAspect::All,
-                base_mip_level: 0,
-                mip_level_count: None,
-                base_array_layer: 0,
-                array_layer_count: None,
-                usage: None,
+            let views = textures.each_ref().map(|texture| {
+                texture.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some("iced_video_player texture view"),
+                    format: None,
+                    dimension: None,
+                    aspect: wgpu::TextureAspect::All,
+                    base_mip_level: 0,
+                    mip_level_count: None,
+                    base_array_layer: 0,
+                    array_layer_count: None,
+                    usage: None,
+                })
             });
 
             let instances = device.create_buffer(&wgpu::BufferDescriptor {
@@ -241,11 +343,11 @@ impl VideoPipeline {
                 entries: &[
                     wgpu::BindGroupEntry {
                         binding: 0,
-                        resource: wgpu::BindingResource::TextureView(&view_y),
+                        resource: wgpu::BindingResource::TextureView(&views[0]),
                     },
                     wgpu::BindGroupEntry {
                         binding: 1,
-                        resource: wgpu::BindingResource::TextureView(&view_uv),
+                        resource: wgpu::BindingResource::TextureView(&views[1]),
                     },
                     wgpu::BindGroupEntry {
                         binding: 2,
@@ -259,12 +361,18 @@ impl VideoPipeline {
                             size: Some(NonZero::new(std::mem::size_of::<Uniforms>() as _).unwrap()),
                         }),
                     },
+                    wgpu::BindGroupEntry {
+                        binding: 4,
+                        resource: wgpu::BindingResource::TextureView(&views[2]),
+                    },
                 ],
             });
 
             entry.insert(VideoEntry {
-                texture_y,
-                texture_uv,
+                textures,
+                format,
+                size: (width, height),
+                colorimetry: frame.colorimetry,
                 instances,
                 bg0: bind_group,
                 alive: Arc::clone(alive),
@@ -275,54 +383,45 @@ impl VideoPipeline {
         }
 
         let VideoEntry {
-            texture_y,
-            texture_uv,
+            textures,
+            colorimetry,
             ..
-        } = self.videos.get(&video_id).unwrap();
-
-        queue.write_texture(
-            wgpu::TexelCopyTextureInfo {
-                texture: texture_y,
-                mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
-                aspect: wgpu::TextureAspect::All,
-            },
-            &frame[..(stride * height) as usize],
-            wgpu::TexelCopyBufferLayout {
-                offset: 0,
-                bytes_per_row: Some(stride),
-                rows_per_image: Some(height),
-            },
-            wgpu::Extent3d {
-                width,
-                height,
-                depth_or_array_layers: 1,
-            },
-        );
-
-        queue.write_texture(
-            wgpu::TexelCopyTextureInfo {
-                texture: texture_uv,
-                mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
-                aspect: wgpu::TextureAspect::All,
-            },
-            &frame[(stride * height) as usize..],
-            wgpu::TexelCopyBufferLayout {
-                offset: 0,
-                bytes_per_row: Some(stride),
-                rows_per_image: Some(height / 2),
-            },
-            wgpu::Extent3d {
-                width: width / 2,
-                height: height / 2,
-                depth_or_array_layers: 1,
-            },
-        );
+        } = self.videos.get_mut(&video_id).unwrap();
+        *colorimetry = frame.colorimetry;
+
+        for (plane, layout) in frame.planes.iter().enumerate().take(format.n_planes()) {
+            let (plane_width, plane_height) = format.plane_size(plane, width, height);
+            let Some(range) =
+                plane_range(format, plane, (width, height), *layout, frame.data().len())
+            else {
+                log::warn!("plane {plane} does not fit in the frame, layout: {layout:?}");
+                return;
+            };
+            queue.write_texture(
+                wgpu::TexelCopyTextureInfo {
+                    texture: &textures[plane],
+                    mip_level: 0,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                &frame.data()[range],
+                wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(layout.stride),
+                    rows_per_image: Some(plane_height),
+                },
+                wgpu::Extent3d {
+                    width: plane_width,
+                    height: plane_height,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
     }
 
     fn prepare(&mut self, queue: &wgpu::Queue, video_id: u64, bounds: &iced_core::Rectangle) {
         if let Some(video) = self.videos.get_mut(&video_id) {
+            let conversion = YuvConversion::new(video.colorimetry);
             let uniforms = Uniforms {
                 rect: [
                     bounds.x,
@@ -330,7 +429,12 @@ impl VideoPipeline {
                     bounds.x + bounds.width,
                     bounds.y + bounds.height,
                 ],
-                _pad: [0; 240],
+                yuv_offset: conversion.offset,
+                yuv_scale: conversion.scale,
+                yuv2rgb: conversion.yuv2rgb,
+                format: shader_format(video.format),
+                transfer: shader_transfer(video.colorimetry.transfer),
+                _pad: [0; 152],
             };
             queue.write_buffer(
                 &video.instances,
@@ -432,16 +536,7 @@ impl Primitive for VideoPrimitive {
                 .expect("lock frame mutex")
                 .clone()
                 .expect("should get data");
-            let stride = frame_guard.stride();
-            pipeline.upload(
-                device,
-                queue,
-                self.video_id,
-                &self.alive,
-                frame_guard.size(),
-                frame_guard.data(),
-                Some(stride),
-            );
+            pipeline.upload(device, queue, self.video_id, &self.alive, &frame_guard);
         }
 
         pipeline.prepare(
@@ -465,3 +560,124 @@ impl Primitive for VideoPrimitive {
         pipeline.draw(target, encoder, clip_bounds, self.video_id);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn padded_nv12_rows() {
+        // 1366 is aligned to 1376 by many decoders
+        let (width, height) = (1366, 768);
+        let y = Plane {
+            offset: 0,
+            stride: 1376,
+        };
+        let uv = Plane {
+            offset: 1376 * 768,
+            stride: 1376,
+        };
+        let len = 1376 * 768 * 3 / 2;
+        assert_eq!(
+            plane_range(PixelFormat::Nv12, 0, (width, height), y, len),
+            Some(0..1376 * 767 + 1366)
+        );
+        assert_eq!(
+            plane_range(PixelFormat::Nv12, 1, (width, height), uv, len),
+            Some(1376 * 768..1376 * 768 + 1376 * 383 + 1366)
+        );
+    }
+
+    #[test]
+    fn odd_i420_planes() {
+        // the default layout of gstreamer, every stride is aligned to 4
+        let (width, height) = (641, 361);
+        let planes = [
+            Plane {
+                offset: 0,
+                stride: 644,
+            },
+            Plane {
+                offset: 644 * 361,
+                stride: 324,
+            },
+            Plane {
+                offset: 644 * 361 + 324 * 181,
+                stride: 324,
+            },
+        ];
+        let len = 644 * 361 + 324 * 181 * 2;
+        assert_eq!(
+            plane_range(PixelFormat::I420, 0, (width, height), planes[0], len),
+            Some(0..644 * 360 + 641)
+        );
+        assert_eq!(
+            plane_range(PixelFormat::I420, 1, (width, height), planes[1], len),
+            Some(644 * 361..644 * 361 + 324 * 180 + 321)
+        );
+        assert_eq!(
+            plane_range(PixelFormat::I420, 2, (width, height), planes[2], len),
+            Some(644 * 361 + 324 * 181..644 * 361 + 324 * 181 + 324 * 180 + 321)
+        );
+    }
+
+    #[test]
+    fn odd_rgba_and_p010_rows() {
+        let rgba = Plane {
+            offset: 0,
+            stride: 1284,
+        };
+        assert_eq!(
+            plane_range(PixelFormat::Rgba, 0, (321, 3), rgba, 1284 * 3),
+            Some(0..1284 * 2 + 321 * 4)
+        );
+
+        let uv = Plane {
+            offset: 0,
+            stride: 644,
+        };
+        // 321 pixels wide gives 161 chroma samples of 4 bytes
+        assert_eq!(
+            plane_range(PixelFormat::P010, 1, (321, 3), uv, 644 * 2),
+            Some(0..644 + 161 * 4)
+        );
+    }
+
+    #[test]
+    fn bt709_limited_conversion() {
+        let conversion = YuvConversion::new(Colorimetry::default());
+        let expected = [
+            [1., 0., 1.5748, 0.],
+            [1., -0.1873, -0.4681, 0.],
+            [1., 1.8556, 0., 0.],
+        ];
+        for (row, expected_row) in conversion.yuv2rgb.iter().zip(expected) {
+            for (value, expected_value) in row.iter().zip(expected_row) {
+                assert!((value - expected_value).abs() < 1e-4);
+            }
+        }
+        assert_eq!(conversion.offset[0], 16. / 255.);
+        assert_eq!(conversion.scale[1], 224. / 255.);
+    }
+
+    #[test]
+    fn broken_layouts() {
+        let short_stride = Plane {
+            offset: 0,
+            stride: 1365,
+        };
+        assert_eq!(
+            plane_range(PixelFormat::Nv12, 0, (1366, 768), short_stride, 1 << 24),
+            None
+        );
+
+        let out_of_frame = Plane {
+            offset: 100,
+            stride: 1376,
+        };
+        assert_eq!(
+            plane_range(PixelFormat::Nv12, 0, (1366, 768), out_of_frame, 1376 * 768),
+            None
+        );
+    }
+}
@@ -0,0 +1,224 @@
+use std::hash::{BuildHasher, Hasher};
+
+/// What happens when the playlist reaches its end
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum RepeatMode {
+    /// Stop after the last item
+    #[default]
+    Off,
+    /// Play the current item again and again
+    One,
+    /// Start again with the first item
+    All,
+}
+
+/// A queue of uris played one after another by [crate::GVideoUrl] without a gap
+#[derive(Debug, Clone, Default)]
+pub struct Playlist {
+    items: Vec<url::Url>,
+    /// the indexes of the items in the order they are played
+    order: Vec<usize>,
+    /// where the current item is in the order
+    current: Option<usize>,
+    /// where the item queued after the current one is in the order, it becomes the current one
+    /// when it starts
+    queued: Option<usize>,
+    repeat: RepeatMode,
+    shuffle: bool,
+}
+
+impl Playlist {
+    /// A playlist which starts with the first item
+    pub fn new<I: IntoIterator<Item = url::Url>>(items: I) -> Self {
+        let items: Vec<url::Url> = items.into_iter().collect();
+        let current = (!items.is_empty()).then_some(0);
+        Self {
+            order: (0..items.len()).collect(),
+            items,
+            current,
+            ..Self::default()
+        }
+    }
+
+    /// Add an item to the end of the playlist
+    pub fn push(&mut self, item: url::Url) {
+        self.order.push(self.items.len());
+        self.items.push(item);
+        self.current.get_or_insert(0);
+    }
+
+    /// The items in the order they are added
+    pub fn items(&self) -> &[url::Url] {
+        &self.items
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// The index of the current item in [Playlist::items]
+    pub fn current_index(&self) -> Option<usize> {
+        self.current.map(|position| self.order[position])
+    }
+
+    /// The uri of the current item
+    pub fn current(&self) -> Option<&url::Url> {
+        self.current_index().map(|index| &self.items[index])
+    }
+
+    pub fn repeat(&self) -> RepeatMode {
+        self.repeat
+    }
+
+    pub fn set_repeat(&mut self, repeat: RepeatMode) {
+        self.repeat = repeat;
+    }
+
+    pub fn shuffle(&self) -> bool {
+        self.shuffle
+    }
+
+    /// Play the items in a random order, the current item stays the current one
+    pub fn set_shuffle(&mut self, shuffle: bool) {
+        let current = self.current_index();
+        self.shuffle = shuffle;
+        self.order = (0..self.items.len()).collect();
+        if shuffle {
+            shuffle_indexes(&mut self.order);
+        }
+        self.current = current.and_then(|index| self.order.iter().position(|&i| i == index));
+        self.queued = None;
+    }
+
+    /// Make the item at the index of [Playlist::items] the current one
+    pub fn jump(&mut self, index: usize) -> Option<&url::Url> {
+        self.current = Some(self.order.iter().position(|&i| i == index)?);
+        self.queued = None;
+        self.current()
+    }
+
+    /// Move to the next item and return it, None at the end of the playlist.
+    /// `automatic` is true when the current item is finished, then [RepeatMode::One] plays it
+    /// again
+    pub fn next(&mut self, automatic: bool) -> Option<&url::Url> {
+        self.current = Some(self.next_position(automatic)?);
+        self.queued = None;
+        self.current()
+    }
+
+    /// Return the next item without moving to it, it becomes the current one with
+    /// [Playlist::start_queued] when it starts to play
+    pub(crate) fn queue_next(&mut self, automatic: bool) -> Option<&url::Url> {
+        self.queued = self.next_position(automatic);
+        self.queued
+            .map(|position| &self.items[self.order[position]])
+    }
+
+    /// Move to the item returned by [Playlist::queue_next], nothing happens if none is queued
+    pub(crate) fn start_queued(&mut self) {
+        if let Some(queued) = self.queued.take() {
+            self.current = Some(queued);
+        }
+    }
+
+    fn next_position(&self, automatic: bool) -> Option<usize> {
+        let current = self.current?;
+        if automatic && self.repeat == RepeatMode::One {
+            Some(current)
+        } else if current + 1 < self.order.len() {
+            Some(current + 1)
+        } else if self.repeat == RepeatMode::All {
+            Some(0)
+        } else {
+            None
+        }
+    }
+
+    /// Move to the previous item and return it, None at the start of the playlist
+    pub fn previous(&mut self) -> Option<&url::Url> {
+        let current = self.current?;
+        self.queued = None;
+        self.current = if current > 0 {
+            Some(current - 1)
+        } else if self.repeat == RepeatMode::All {
+            Some(self.order.len() - 1)
+        } else {
+            return None;
+        };
+        self.current()
+    }
+}
+
+/// Fisher-Yates with the random keys of the std hasher, so no rng crate is needed
+fn shuffle_indexes(indexes: &mut [usize]) {
+    let random_state = std::collections::hash_map::RandomState::new();
+    for i in (1..indexes.len()).rev() {
+        let mut hasher = random_state.build_hasher();
+        hasher.write_usize(i);
+        let j = (hasher.finish() % (i as u64 + 1)) as usize;
+        indexes.swap(i, j);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn playlist(len: usize) -> Playlist {
+        Playlist::new((0..len).map(|i| url::Url::parse(&format!("file:///{i}.mp4")).unwrap()))
+    }
+
+    #[test]
+    fn next_stops_at_the_end_without_repeat() {
+        let mut playlist = playlist(2);
+        assert_eq!(playlist.next(true).map(|url| url.path()), Some("/1.mp4"));
+        assert_eq!(playlist.next(true), None);
+        assert_eq!(playlist.current_index(), Some(1));
+    }
+
+    #[test]
+    fn repeat_modes() {
+        let mut playlist = playlist(2);
+        playlist.set_repeat(RepeatMode::One);
+        assert_eq!(playlist.next(true).map(|url| url.path()), Some("/0.mp4"));
+        assert_eq!(playlist.next(false).map(|url| url.path()), Some("/1.mp4"));
+        playlist.set_repeat(RepeatMode::All);
+        assert_eq!(playlist.next(true).map(|url| url.path()), Some("/0.mp4"));
+        assert_eq!(playlist.previous().map(|url| url.path()), Some("/1.mp4"));
+    }
+
+    #[test]
+    fn queued_item_becomes_current_when_started() {
+        let mut playlist = playlist(3);
+        assert_eq!(
+            playlist.queue_next(true).map(|url| url.path()),
+            Some("/1.mp4")
+        );
+        assert_eq!(playlist.current_index(), Some(0));
+        playlist.start_queued();
+        assert_eq!(playlist.current_index(), Some(1));
+        // a second stream start without a queued item keeps it
+        playlist.start_queued();
+        assert_eq!(playlist.current_index(), Some(1));
+        playlist.queue_next(true);
+        assert_eq!(playlist.previous().map(|url| url.path()), Some("/0.mp4"));
+        playlist.start_queued();
+        assert_eq!(playlist.current_index(), Some(0));
+    }
+
+    #[test]
+    fn shuffle_keeps_the_current_item() {
+        let mut playlist = playlist(10);
+        playlist.jump(4);
+        playlist.set_shuffle(true);
+        assert_eq!(playlist.current_index(), Some(4));
+        let mut played = vec![];
+        playlist.set_repeat(RepeatMode::All);
+        for _ in 0..10 {
+            played.push(playlist.next(false).map(|url| url.path().to_owned()));
+        }
+        played.sort();
+        played.dedup();
+        assert_eq!(played.len(), 10);
+    }
+}
@@ -0,0 +1,1242 @@
+use super::IcedGStreamerError;
+use gst::glib;
+use gst::prelude::*;
+use gstreamer as gst;
+use gstreamer_app as gst_app;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// The codec used to encode the recorded video
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum VideoCodec {
+    /// H.264 with x264enc
+    #[default]
+    X264,
+    /// H.265 with x265enc
+    X265,
+    /// VP8 with vp8enc
+    Vp8,
+    /// VP9 with vp9enc
+    Vp9,
+    /// AV1 with av1enc
+    Av1,
+    /// H.264 with openh264enc
+    OpenH264,
+}
+
+impl VideoCodec {
+    /// the name of the gstreamer element
+    pub fn encoder_name(&self) -> &'static str {
+        match self {
+            Self::X264 => "x264enc",
+            Self::X265 => "x265enc",
+            Self::Vp8 => "vp8enc",
+            Self::Vp9 => "vp9enc",
+            Self::Av1 => "av1enc",
+            Self::OpenH264 => "openh264enc",
+        }
+    }
+
+    fn parser_name(&self) -> Option<&'static str> {
+        match self {
+            Self::X264 | Self::OpenH264 => Some("h264parse"),
+            Self::X265 => Some("h265parse"),
+            Self::Vp8 | Self::Vp9 | Self::Av1 => None,
+        }
+    }
+
+    /// if the encoder is installed
+    pub fn is_available(&self) -> bool {
+        gst::init().is_ok() && gst::ElementFactory::find(self.encoder_name()).is_some()
+    }
+}
+
+/// The container of the recorded file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Container {
+    Mp4,
+    Mkv,
+    Webm,
+    Mov,
+    Ts,
+    Flv,
+    Avi,
+}
+
+impl Container {
+    /// guess the container from the extension of the file
+    pub fn from_extension(extension: &OsStr) -> Option<Self> {
+        let extension = extension.to_str()?.to_ascii_lowercase();
+        Some(match extension.as_str() {
+            "mp4" => Self::Mp4,
+            "mkv" => Self::Mkv,
+            "webm" => Self::Webm,
+            "mov" => Self::Mov,
+            "ts" => Self::Ts,
+            "flv" => Self::Flv,
+            "avi" => Self::Avi,
+            _ => return None,
+        })
+    }
+
+    /// the name of the gstreamer element
+    pub fn muxer_name(&self) -> &'static str {
+        match self {
+            Self::Mp4 => "mp4mux",
+            Self::Mkv => "matroskamux",
+            Self::Webm => "webmmux",
+            Self::Mov => "qtmux",
+            Self::Ts => "mpegtsmux",
+            Self::Flv => "flvmux",
+            Self::Avi => "avimux",
+        }
+    }
+}
+
+/// The codec used to encode the recorded audio
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AudioCodec {
+    /// Opus with opusenc
+    Opus,
+    /// AAC with the first of fdkaacenc, avenc_aac or voaacenc which is installed
+    Aac,
+    /// Vorbis with vorbisenc
+    Vorbis,
+}
+
+impl AudioCodec {
+    /// the name of the gstreamer element
+    pub fn encoder_name(&self) -> &'static str {
+        match self {
+            Self::Opus => "opusenc",
+            // the registry can only be searched after gstreamer is initialized
+            Self::Aac => ["fdkaacenc", "avenc_aac", "voaacenc"]
+                .into_iter()
+                .find(|name| gst::init().is_ok() && gst::ElementFactory::find(name).is_some())
+                .unwrap_or("avenc_aac"),
+            Self::Vorbis => "vorbisenc",
+        }
+    }
+
+    /// if the encoder is installed
+    pub fn is_available(&self) -> bool {
+        gst::init().is_ok() && gst::ElementFactory::find(self.encoder_name()).is_some()
+    }
+
+    /// the codec used when it is not set in the [RecordingOptions]
+    fn default_for(container: Container) -> Self {
+        match container {
+            Container::Mkv | Container::Webm => Self::Opus,
+            Container::Mp4 | Container::Mov | Container::Ts | Container::Flv | Container::Avi => {
+                Self::Aac
+            }
+        }
+    }
+}
+
+/// How fast the encoder should be, the slower ones give smaller files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EncoderPreset {
+    UltraFast,
+    Fast,
+    Medium,
+    Slow,
+}
+
+/// How the encoder decides the size of the frames
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RateControl {
+    /// target bitrate in kbit/s
+    Bitrate(u32),
+    /// constant quality, from 0 to 100, the higher the better
+    Quality(u8),
+}
+
+/// How mp4 and mov files are written, a standard mp4 file can not be played if the app is killed
+/// before the end of the recording, as its index (the moov atom) is written at the end
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Mp4Mode {
+    /// the index is written at the end
+    #[default]
+    Standard,
+    /// written in fragments of this duration by mp4mux, at most one fragment is lost
+    Fragmented(std::time::Duration),
+    /// space for the index of up to `max_duration` of recording is reserved at the beginning of
+    /// the file, and it is updated every `update_period`
+    Robust {
+        max_duration: std::time::Duration,
+        update_period: std::time::Duration,
+    },
+    /// written in fragments of this duration by isofmp4mux from gst-plugins-rs
+    Isofmp4(std::time::Duration),
+}
+
+/// The options of the recording, used with `save_file`
+/// If the container is not set, it is guessed from the extension of the file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct RecordingOptions {
+    codec: VideoCodec,
+    container: Option<Container>,
+    rate_control: Option<RateControl>,
+    keyframe_interval: Option<u32>,
+    preset: Option<EncoderPreset>,
+    audio_codec: Option<AudioCodec>,
+    without_audio: bool,
+    segment_duration: Option<std::time::Duration>,
+    segment_size: Option<u64>,
+    mp4_mode: Mp4Mode,
+}
+
+impl RecordingOptions {
+    /// the default options, x264 with the container from the extension
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// set the [VideoCodec]
+    pub fn codec(self, codec: VideoCodec) -> Self {
+        Self { codec, ..self }
+    }
+
+    /// set the [Container], instead of guessing it from the extension
+    pub fn container(self, container: Container) -> Self {
+        Self {
+            container: Some(container),
+            ..self
+        }
+    }
+
+    /// set the target bitrate in kbit/s
+    pub fn bitrate(self, kbps: u32) -> Self {
+        Self {
+            rate_control: Some(RateControl::Bitrate(kbps)),
+            ..self
+        }
+    }
+
+    /// set a constant quality from 0 to 100, the higher the better
+    pub fn quality(self, quality: u8) -> Self {
+        Self {
+            rate_control: Some(RateControl::Quality(quality.min(100))),
+            ..self
+        }
+    }
+
+    /// set the max distance between two keyframes, in frames
+    pub fn keyframe_interval(self, frames: u32) -> Self {
+        Self {
+            keyframe_interval: Some(frames),
+            ..self
+        }
+    }
+
+    /// set the [EncoderPreset]
+    pub fn preset(self, preset: EncoderPreset) -> Self {
+        Self {
+            preset: Some(preset),
+            ..self
+        }
+    }
+
+    /// set the [AudioCodec], instead of choosing it from the container
+    /// If it is set and the encoder is not installed, the recording fails
+    pub fn audio_codec(self, audio_codec: AudioCodec) -> Self {
+        Self {
+            audio_codec: Some(audio_codec),
+            ..self
+        }
+    }
+
+    /// only record the video, even if the stream has sound
+    pub fn without_audio(self) -> Self {
+        Self {
+            without_audio: true,
+            ..self
+        }
+    }
+
+    /// Split the recording in files of at most this duration, see [RecordingOptions::is_segmented]
+    pub fn max_segment_duration(self, duration: std::time::Duration) -> Self {
+        Self {
+            segment_duration: Some(duration),
+            ..self
+        }
+    }
+
+    /// Split the recording in files of at most this size in bytes, see
+    /// [RecordingOptions::is_segmented]
+    pub fn max_segment_size(self, bytes: u64) -> Self {
+        Self {
+            segment_size: Some(bytes),
+            ..self
+        }
+    }
+
+    /// If the recording is split in several files with splitmuxsink. The file name is used as a
+    /// printf pattern, like `record-%05d.mp4`, if it has no `%`, `-%05d` is added before the
+    /// extension. If the app crashes, only the last segment is lost.
+    pub fn is_segmented(&self) -> bool {
+        self.segment_duration.is_some() || self.segment_size.is_some()
+    }
+
+    /// set the [Mp4Mode], it is only used with [Container::Mp4] and [Container::Mov]
+    ///
+    /// ```
+    /// use gstreamer_iced::{Mp4Mode, RecordingOptions};
+    /// use std::time::Duration;
+    ///
+    /// // at most one second is lost if the app is killed
+    /// let options = RecordingOptions::new().mp4_mode(Mp4Mode::Fragmented(Duration::from_secs(1)));
+    /// ```
+    pub fn mp4_mode(self, mp4_mode: Mp4Mode) -> Self {
+        Self { mp4_mode, ..self }
+    }
+
+    /// the container of the file, from the options or from the extension
+    pub fn container_for(&self, file: &Path) -> Result<Container, IcedGStreamerError> {
+        if let Some(container) = self.container {
+            return Ok(container);
+        }
+        let extension = file.extension().ok_or(IcedGStreamerError::NoExtension)?;
+        Container::from_extension(extension).ok_or(IcedGStreamerError::UnsupportedExtension)
+    }
+
+    /// the name of the gstreamer element of the muxer
+    fn muxer_name(&self, container: Container) -> &'static str {
+        match (container, self.mp4_mode) {
+            (Container::Mp4 | Container::Mov, Mp4Mode::Isofmp4(_)) => "isofmp4mux",
+            _ => container.muxer_name(),
+        }
+    }
+
+    fn configure_muxer(&self, container: Container, muxer: &gst::Element) {
+        if !matches!(container, Container::Mp4 | Container::Mov) {
+            return;
+        }
+        match self.mp4_mode {
+            Mp4Mode::Standard => {}
+            Mp4Mode::Fragmented(duration) => {
+                // in milliseconds
+                set_element_property(
+                    muxer,
+                    "fragment-duration",
+                    &duration.as_millis().max(1).to_string(),
+                );
+            }
+            Mp4Mode::Robust {
+                max_duration,
+                update_period,
+            } => {
+                set_element_property(
+                    muxer,
+                    "reserved-max-duration",
+                    &max_duration.as_nanos().to_string(),
+                );
+                set_element_property(
+                    muxer,
+                    "reserved-moov-update-period",
+                    &update_period.as_nanos().to_string(),
+                );
+            }
+            Mp4Mode::Isofmp4(duration) => {
+                set_element_property(muxer, "fragment-duration", &duration.as_nanos().to_string());
+            }
+        }
+    }
+
+    fn configure_encoder(&self, encoder: &gst::Element) {
+        // a quality from 0 to 100 to a quantizer from max to 0
+        let quantizer = |max: u32, quality: u8| max - max * quality as u32 / 100;
+        match self.codec {
+            VideoCodec::X264 | VideoCodec::X265 => {
+                set_element_property(encoder, "tune", "zerolatency");
+                match self.rate_control {
+                    Some(RateControl::Bitrate(kbps)) => {
+                        set_element_property(encoder, "bitrate", &kbps.to_string());
+                    }
+                    Some(RateControl::Quality(quality)) if self.codec == VideoCodec::X264 => {
+                        set_element_property(encoder, "pass", "quant");
+                        set_element_property(
+                            encoder,
+                            "quantizer",
+                            &quantizer(50, quality).to_string(),
+                        );
+                    }
+                    Some(RateControl::Quality(quality)) => {
+                        set_element_property(encoder, "qp", &quantizer(51, quality).to_string());
+                    }
+                    None => {}
+                }
+                if let Some(frames) = self.keyframe_interval {
+                    set_element_property(encoder, "key-int-max", &frames.to_string());
+                }
+                if let Some(preset) = self.preset {
+                    let preset = match preset {
+                        EncoderPreset::UltraFast => "ultrafast",
+                        EncoderPreset::Fast => "veryfast",
+                        EncoderPreset::Medium => "medium",
+                        EncoderPreset::Slow => "slow",
+                    };
+                    set_element_property(encoder, "speed-preset", preset);
+                }
+            }
+            VideoCodec::Vp8 | VideoCodec::Vp9 | VideoCodec::Av1 => {
+                match self.rate_control {
+                    Some(RateControl::Bitrate(kbps)) => {
+                        set_element_property(encoder, "end-usage", "vbr");
+                        // vpx takes bit/s, aom takes kbit/s
+                        let bitrate = if self.codec == VideoCodec::Av1 {
+                            kbps
+                        } else {
+                            kbps.saturating_mul(1000)
+                        };
+                        set_element_property(encoder, "target-bitrate", &bitrate.to_string());
+                    }
+                    Some(RateControl::Quality(quality)) => {
+                        set_element_property(encoder, "end-usage", "q");
+                        set_element_property(
+                            encoder,
+                            "cq-level",
+                            &quantizer(63, quality).to_string(),
+                        );
+                    }
+                    None => {}
+                }
+                if let Some(frames) = self.keyframe_interval {
+                    set_element_property(encoder, "keyframe-max-dist", &frames.to_string());
+                }
+                if let Some(preset) = self.preset {
+                    let cpu_used = match preset {
+                        EncoderPreset::UltraFast => 8,
+                        EncoderPreset::Fast => 6,
+                        EncoderPreset::Medium => 4,
+                        EncoderPreset::Slow => 1,
+                    };
+                    set_element_property(encoder, "cpu-used", &cpu_used.to_string());
+                    if self.codec != VideoCodec::Av1
+                        && matches!(preset, EncoderPreset::UltraFast | EncoderPreset::Fast)
+                    {
+                        // realtime
+                        set_element_property(encoder, "deadline", "1");
+                    }
+                }
+            }
+            VideoCodec::OpenH264 => {
+                match self.rate_control {
+                    Some(RateControl::Bitrate(kbps)) => {
+                        set_element_property(encoder, "rate-control", "bitrate");
+                        set_element_property(
+                            encoder,
+                            "bitrate",
+                            &kbps.saturating_mul(1000).to_string(),
+                        );
+                    }
+                    Some(RateControl::Quality(quality)) => {
+                        set_element_property(encoder, "rate-control", "quality");
+                        set_element_property(
+                            encoder,
+                            "qp-max",
+                            &quantizer(51, quality).to_string(),
+                        );
+                    }
+                    None => {}
+                }
+                if let Some(frames) = self.keyframe_interval {
+                    set_element_property(encoder, "gop-size", &frames.to_string());
+                }
+                if let Some(preset) = self.preset {
+                    let complexity = match preset {
+                        EncoderPreset::UltraFast | EncoderPreset::Fast => "low",
+                        EncoderPreset::Medium => "medium",
+                        EncoderPreset::Slow => "high",
+                    };
+                    set_element_property(encoder, "complexity", complexity);
+                }
+            }
+        }
+    }
+}
+
+/// the property may not exist in old versions of the plugins, and its range may be smaller, the
+/// values which do not fit are skipped instead of panicking
+fn set_element_property(element: &gst::Element, name: &str, value: &str) {
+    let Some(pspec) = element.find_property(name) else {
+        log::warn!("{} has no property {name}", element.name());
+        return;
+    };
+    match glib::Value::deserialize(value, pspec.value_type()) {
+        Ok(parsed) if in_range(&pspec, &parsed) => element.set_property_from_value(name, &parsed),
+        _ => log::warn!("{value} is not a valid {name} of {}", element.name()),
+    }
+}
+
+/// if the value is inside the minimum and the maximum of a numeric property, the other
+/// properties have no range
+fn in_range(pspec: &glib::ParamSpec, value: &glib::Value) -> bool {
+    fn check<'a, T: PartialOrd + glib::value::FromValue<'a>>(
+        value: &'a glib::Value,
+        minimum: T,
+        maximum: T,
+    ) -> bool {
+        value
+            .get::<T>()
+            .is_ok_and(|value| minimum <= value && value <= maximum)
+    }
+    if let Some(pspec) = pspec.downcast_ref::<glib::ParamSpecInt>() {
+        check(value, pspec.minimum(), pspec.maximum())
+    } else if let Some(pspec) = pspec.downcast_ref::<glib::ParamSpecUInt>() {
+        check(value, pspec.minimum(), pspec.maximum())
+    } else if let Some(pspec) = pspec.downcast_ref::<glib::ParamSpecInt64>() {
+        check(value, pspec.minimum(), pspec.maximum())
+    } else if let Some(pspec) = pspec.downcast_ref::<glib::ParamSpecUInt64>() {
+        check(value, pspec.minimum(), pspec.maximum())
+    } else if let Some(pspec) = pspec.downcast_ref::<glib::ParamSpecDouble>() {
+        check(value, pspec.minimum(), pspec.maximum())
+    } else {
+        true
+    }
+}
+
+/// make an element, report which one is missing if the plugin is not installed
+pub(crate) fn make_element(factory: &'static str) -> Result<gst::Element, IcedGStreamerError> {
+    if gst::ElementFactory::find(factory).is_none() {
+        return Err(IcedGStreamerError::MissingElement(factory));
+    }
+    Ok(gst::ElementFactory::make(factory).build()?)
+}
+
+/// The name of the element message posted by splitmuxsink when a segment is written
+pub(crate) const SEGMENT_CLOSED_MESSAGE: &str = "splitmuxsink-fragment-closed";
+
+/// The name of the application message posted when the file of a recording is finished
+pub(crate) const RECORDING_FINISHED_MESSAGE: &str = "gstreamer-iced-recording-finished";
+
+/// the file name of the segments, with a `%05d` before the extension if there is no pattern
+fn segment_pattern(file: &Path) -> PathBuf {
+    if file.to_string_lossy().contains('%') {
+        return file.to_path_buf();
+    }
+    let mut name = file.file_stem().unwrap_or_default().to_os_string();
+    name.push("-%05d");
+    if let Some(extension) = file.extension() {
+        name.push(".");
+        name.push(extension);
+    }
+    file.with_file_name(name)
+}
+
+/// The elements of a recording branch
+pub(crate) struct RecordingBranch {
+    /// from the videoconvert to the filesink, or to the splitmuxsink
+    pub elements: Vec<gst::Element>,
+    /// the muxer, or the splitmuxsink
+    pub muxer: gst::Element,
+    pub container: Container,
+    filesink: gst::Element,
+    segmented: bool,
+}
+
+impl RecordingBranch {
+    /// link the audio coming from the encoder to the muxer
+    fn link_audio(
+        &self,
+        upstream: &gst::Element,
+        encoder: &gst::Element,
+        codec: AudioCodec,
+    ) -> Result<(), IcedGStreamerError> {
+        // the appsrc has no caps yet, so a plain link could pick the video pad of the muxer
+        let linked = if self.segmented {
+            let muxer = self.muxer.property::<gst::Element>("muxer");
+            muxer_audio_pad(&muxer, encoder).is_some()
+                && upstream
+                    .link_pads(None, &self.muxer, Some("audio_%u"))
+                    .is_ok()
+        } else {
+            muxer_audio_pad(&self.muxer, encoder).is_some_and(|pad| {
+                upstream
+                    .link_pads(None, &self.muxer, Some(pad.as_str()))
+                    .is_ok()
+            })
+        };
+        if !linked {
+            return Err(IcedGStreamerError::IncompatibleAudioContainer(
+                codec,
+                self.container,
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// The template of the muxer for the audio of the encoder, the audio templates are preferred, as
+/// some muxers like mpegtsmux only have generic sink pads
+fn muxer_audio_pad(muxer: &gst::Element, encoder: &gst::Element) -> Option<String> {
+    let caps = encoder.static_pad("src")?.query_caps(None);
+    let templates: Vec<gst::PadTemplate> = muxer
+        .pad_template_list()
+        .into_iter()
+        .filter(|template| {
+            template.direction() == gst::PadDirection::Sink && template.caps().can_intersect(&caps)
+        })
+        .collect();
+    templates
+        .iter()
+        .find(|template| template.name_template().starts_with("audio"))
+        .or(templates.first())
+        .map(|template| template.name_template().to_string())
+}
+
+/// splitmuxsink only links its muxer once the first buffer comes, check the caps before
+fn muxer_accepts(muxer: &gst::Element, upstream: &gst::Element) -> bool {
+    let Some(caps) = upstream.static_pad("src").map(|pad| pad.query_caps(None)) else {
+        return false;
+    };
+    muxer
+        .pad_template_list()
+        .iter()
+        .filter(|template| template.direction() == gst::PadDirection::Sink)
+        .any(|template| template.caps().can_intersect(&caps))
+}
+
+/// Create the elements from the videoconvert to the filesink, add them to the bin, and link them
+/// after the upstream element
+pub(crate) fn link_recording_branch(
+    bin: &impl IsA<gst::Bin>,
+    upstream: &gst::Element,
+    file: &Path,
+    options: &RecordingOptions,
+) -> Result<RecordingBranch, IcedGStreamerError> {
+    let container = options.container_for(file)?;
+
+    let videoconvert = make_element("videoconvert")?;
+    let encoder = make_element(options.codec.encoder_name())?;
+    options.configure_encoder(&encoder);
+    let parser = match options.codec.parser_name() {
+        // the parser is only needed to convert the stream format, skip it if it is not installed
+        Some(parser) if gst::ElementFactory::find(parser).is_some() => Some(make_element(parser)?),
+        _ => None,
+    };
+    let muxer = make_element(options.muxer_name(container))?;
+    options.configure_muxer(container, &muxer);
+    let filesink = make_element("filesink")?;
+    // the branch may be added to a running pipeline, it should not wait for a preroll
+    filesink.set_property("async", false);
+
+    let mut elements = vec![videoconvert, encoder];
+    elements.extend(parser);
+    let incompatible = || IcedGStreamerError::IncompatibleContainer(options.codec, container);
+
+    if options.is_segmented() {
+        if !muxer_accepts(&muxer, elements.last().unwrap()) {
+            return Err(incompatible());
+        }
+        let location = segment_pattern(file);
+        let splitmuxsink = make_element("splitmuxsink")?;
+        splitmuxsink.set_property(
+            "location",
+            location.to_str().ok_or(IcedGStreamerError::Uri)?,
+        );
+        splitmuxsink.set_property("muxer", &muxer);
+        splitmuxsink.set_property("sink", &filesink);
+        if let Some(duration) = options.segment_duration {
+            splitmuxsink.set_property("max-size-time", duration.as_nanos() as u64);
+        }
+        if let Some(bytes) = options.segment_size {
+            splitmuxsink.set_property("max-size-bytes", bytes);
+        } else {
+            // only cut at the max duration, ask the encoder for a keyframe there
+            splitmuxsink.set_property("send-keyframe-requests", true);
+        }
+
+        bin.add_many(&elements)?;
+        bin.add(&splitmuxsink)?;
+        gst::Element::link_many(&elements)?;
+        elements
+            .last()
+            .unwrap()
+            .link_pads(None, &splitmuxsink, Some("video"))
+            .map_err(|_| incompatible())?;
+        upstream.link(&elements[0])?;
+        elements.push(splitmuxsink.clone());
+
+        return Ok(RecordingBranch {
+            elements,
+            muxer: splitmuxsink,
+            container,
+            filesink,
+            segmented: true,
+        });
+    }
+
+    filesink.set_property("location", file.to_str().ok_or(IcedGStreamerError::Uri)?);
+    elements.push(muxer.clone());
+    elements.push(filesink.clone());
+
+    bin.add_many(&elements)?;
+    gst::Element::link_many(&elements[..elements.len() - 2])?;
+    elements[elements.len() - 3]
+        .link(&muxer)
+        .map_err(|_| incompatible())?;
+    muxer.link(&filesink)?;
+    upstream.link(&elements[0])?;
+
+    Ok(RecordingBranch {
+        elements,
+        muxer,
+        container,
+        filesink,
+        segmented: false,
+    })
+}
+
+/// The name of the tee the recordings are attached to, in the video bin and in the audio bin
+pub(crate) const TEE_NAME: &str = "tee";
+
+/// The state of the recording of a [crate::GVideo]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum RecordingState {
+    /// nothing is being recorded
+    #[default]
+    Stopped,
+    Recording,
+    /// the file is kept open, but the buffers are dropped
+    Paused,
+}
+
+/// The running times when a recording is paused and resumed, so the timestamps after a pause
+/// follow the ones before it, and the file has no gap. The video and the audio share it, so they
+/// are shifted by the same time
+#[derive(Debug, Default)]
+struct PauseClock {
+    /// the start and the end of every pause, the end is None while it is paused
+    pauses: Vec<(gst::ClockTime, Option<gst::ClockTime>)>,
+}
+
+impl PauseClock {
+    fn pause(&mut self, now: gst::ClockTime) {
+        if !self.is_paused() {
+            self.pauses.push((now, None));
+        }
+    }
+
+    fn resume(&mut self, now: gst::ClockTime) {
+        if let Some((start, end @ None)) = self.pauses.last_mut() {
+            *end = Some(now.max(*start));
+        }
+    }
+
+    fn is_paused(&self) -> bool {
+        matches!(self.pauses.last(), Some((_, None)))
+    }
+
+    /// Return the time to remove from the running time of the buffer, or None if the buffer
+    /// is inside a pause and should be dropped
+    fn offset(&self, running_time: gst::ClockTime) -> Option<gst::ClockTime> {
+        let mut offset = gst::ClockTime::ZERO;
+        for &(start, end) in &self.pauses {
+            if running_time < start {
+                break;
+            }
+            match end {
+                Some(end) if running_time >= end => offset += end - start,
+                _ => return None,
+            }
+        }
+        Some(offset)
+    }
+}
+
+/// A recording attached to the tee of a running pipeline
+#[derive(Debug)]
+pub(crate) struct Recording {
+    video: AttachedBranch,
+    audio: Option<AttachedBranch>,
+    clock: Arc<Mutex<PauseClock>>,
+}
+
+impl Recording {
+    /// Attach a recording to the tee of the video bin, and to the tee of the audio bin if there
+    /// is one. The timestamps of the file begin with zero.
+    pub(crate) fn start(
+        video_bin: &gst::Bin,
+        audio_bin: Option<&gst::Bin>,
+        file: &Path,
+        options: &RecordingOptions,
+    ) -> Result<Self, IcedGStreamerError> {
+        let start = video_bin
+            .current_running_time()
+            .unwrap_or(gst::ClockTime::ZERO);
+
+        let clock = Arc::new(Mutex::new(PauseClock::default()));
+
+        let bin = gst::Bin::new();
+        let queue = make_element("queue")?;
+        bin.add(&queue)?;
+        let branch = link_recording_branch(&bin, &queue, file, options)?;
+        drop_paused_buffers(&queue.static_pad("src").unwrap(), clock.clone(), start);
+
+        // once the file is finished, the branch is not needed anymore. splitmuxsink also sends
+        // EOS to the filesink at the end of every segment, so wait for the EOS of the branch
+        let ended = Arc::new(AtomicBool::new(false));
+        let ended_i = ended.clone();
+        branch.elements[0].static_pad("sink").unwrap().add_probe(
+            gst::PadProbeType::EVENT_DOWNSTREAM,
+            move |_, info| match info.event() {
+                Some(event) if event.type_() == gst::EventType::Eos => {
+                    ended_i.store(true, Ordering::SeqCst);
+                    gst::PadProbeReturn::Remove
+                }
+                _ => gst::PadProbeReturn::Ok,
+            },
+        );
+        let bin_weak = bin.downgrade();
+        let location = file.to_string_lossy().into_owned();
+        branch.filesink.static_pad("sink").unwrap().add_probe(
+            gst::PadProbeType::EVENT_DOWNSTREAM,
+            move |_, info| match info.event() {
+                Some(event)
+                    if event.type_() == gst::EventType::Eos && ended.load(Ordering::SeqCst) =>
+                {
+                    if let Some(bin) = bin_weak.upgrade() {
+                        let structure = gst::Structure::builder(RECORDING_FINISHED_MESSAGE)
+                            .field("location", &location)
+                            .build();
+                        let _ = bin.post_message(
+                            gst::message::Application::builder(structure)
+                                .src(&bin)
+                                .build(),
+                        );
+                        remove_later(bin);
+                    }
+                    gst::PadProbeReturn::Remove
+                }
+                _ => gst::PadProbeReturn::Ok,
+            },
+        );
+
+        let audio = match audio_bin {
+            Some(audio_bin) => {
+                attach_audio(audio_bin, &bin, &branch, options, start, clock.clone())?
+            }
+            None => None,
+        };
+        let video = AttachedBranch::attach(video_bin, bin, &queue, start)?;
+        Ok(Self {
+            video,
+            audio,
+            clock,
+        })
+    }
+
+    pub(crate) fn state(&self) -> RecordingState {
+        if self.clock.lock().unwrap().is_paused() {
+            RecordingState::Paused
+        } else {
+            RecordingState::Recording
+        }
+    }
+
+    /// Drop the buffers of the recording from the running time of the pipeline, or keep them
+    /// again
+    pub(crate) fn set_paused(&self, paused: bool) {
+        let now = self
+            .video
+            .tee
+            .current_running_time()
+            .unwrap_or(gst::ClockTime::ZERO);
+        let mut clock = self.clock.lock().unwrap();
+        if paused {
+            clock.pause(now);
+        } else {
+            clock.resume(now);
+        }
+    }
+
+    /// Detach the recording from the tees and finish the file, the preview keeps running
+    pub(crate) fn stop(self) {
+        self.video.detach();
+        if let Some(audio) = self.audio {
+            audio.detach();
+        }
+    }
+}
+
+/// A bin linked to a request pad of a tee
+#[derive(Debug)]
+struct AttachedBranch {
+    tee: gst::Element,
+    tee_pad: gst::Pad,
+}
+
+impl AttachedBranch {
+    fn attach(
+        parent: &gst::Bin,
+        bin: gst::Bin,
+        first: &gst::Element,
+        start: gst::ClockTime,
+    ) -> Result<Self, IcedGStreamerError> {
+        let tee = parent.by_name(TEE_NAME).ok_or(IcedGStreamerError::Cast)?;
+
+        let staticpad = first.static_pad("sink").unwrap();
+        let sinkgost = gst::GhostPad::builder_with_target(&staticpad)?.build();
+        sinkgost.set_active(true)?;
+        sinkgost.set_offset(-(start.nseconds() as i64));
+        bin.add_pad(&sinkgost)?;
+
+        parent.add(&bin)?;
+        bin.sync_state_with_parent()?;
+
+        let tee_pad = tee
+            .request_pad_simple("src_%u")
+            .ok_or(IcedGStreamerError::Cast)?;
+        tee_pad
+            .link(&sinkgost)
+            .map_err(|_| IcedGStreamerError::Cast)?;
+        Ok(Self { tee, tee_pad })
+    }
+
+    /// Unlink the branch when no buffer is flowing, then send EOS only to the branch
+    fn detach(self) {
+        let Self { tee, tee_pad } = self;
+        tee_pad.add_probe(gst::PadProbeType::IDLE, move |pad, _| {
+            if let Some(peer) = pad.peer() {
+                let _ = pad.unlink(&peer);
+                peer.send_event(gst::event::Eos::new());
+            }
+            tee.release_request_pad(pad);
+            gst::PadProbeReturn::Remove
+        });
+    }
+}
+
+/// Drop the buffers going through the pad while the recording is paused. When it is resumed,
+/// a segment shifted by the paused time is sent downstream before the next buffer.
+/// The running time in the bin begins with zero at the start of the recording, the pauses are in
+/// the running time of the pipeline.
+fn drop_paused_buffers(pad: &gst::Pad, clock: Arc<Mutex<PauseClock>>, start: gst::ClockTime) {
+    // the offset of the last segment sent downstream
+    let offset = Mutex::new(gst::ClockTime::ZERO);
+    let segment = Mutex::new(None::<gst::FormattedSegment<gst::ClockTime>>);
+    pad.add_probe(
+        gst::PadProbeType::BUFFER | gst::PadProbeType::EVENT_DOWNSTREAM,
+        move |pad, info| {
+            if let Some(gst::EventView::Segment(event)) = info.event().map(|event| event.view()) {
+                let Some(upstream) = event.segment().downcast_ref::<gst::ClockTime>().cloned()
+                else {
+                    return gst::PadProbeReturn::Ok;
+                };
+                let shifted = shift_segment(&upstream, *offset.lock().unwrap());
+                *segment.lock().unwrap() = Some(upstream);
+                info.data = Some(gst::PadProbeData::Event(gst::event::Segment::new(&shifted)));
+                return gst::PadProbeReturn::Ok;
+            }
+            let Some(buffer) = info.buffer() else {
+                return gst::PadProbeReturn::Ok;
+            };
+            let segment = segment.lock().unwrap();
+            let Some(running_time) = segment
+                .as_ref()
+                .and_then(|segment| segment.to_running_time(buffer.pts()?))
+            else {
+                return gst::PadProbeReturn::Ok;
+            };
+            let Some(paused_total) = clock.lock().unwrap().offset(running_time + start) else {
+                return gst::PadProbeReturn::Drop;
+            };
+            let mut offset = offset.lock().unwrap();
+            if paused_total != *offset
+                && let (Some(segment), Some(peer)) = (segment.as_ref(), pad.peer())
+            {
+                *offset = paused_total;
+                peer.send_event(gst::event::Segment::new(&shift_segment(
+                    segment,
+                    paused_total,
+                )));
+            }
+            gst::PadProbeReturn::Ok
+        },
+    );
+}
+
+/// move the running time of the segment back by the paused time
+fn shift_segment(
+    segment: &gst::FormattedSegment<gst::ClockTime>,
+    paused_total: gst::ClockTime,
+) -> gst::FormattedSegment<gst::ClockTime> {
+    let mut shifted = segment.clone();
+    if let Err(err) = shifted.offset_running_time(-(paused_total.nseconds() as i64)) {
+        log::warn!("failed to shift the segment of the recording: {err}");
+    }
+    shifted
+}
+
+/// The state of a bin can not be changed from its own streaming thread
+fn remove_later(bin: gst::Bin) {
+    std::thread::spawn(move || {
+        let _ = bin.set_state(gst::State::Null);
+        if let Some(parent) = bin.parent().and_then(|p| p.downcast::<gst::Bin>().ok()) {
+            let _ = parent.remove(&bin);
+        }
+    });
+}
+
+/// Attach the audio part of a recording to the audio sink of playbin. The sound is encoded in the
+/// audio bin, and the encoded buffers are pushed to the muxer of the video branch with an appsrc,
+/// as the audio sink and the video sink of playbin are not in the same bin.
+/// Return None if the audio should not be recorded.
+fn attach_audio(
+    audio_bin: &gst::Bin,
+    video_record_bin: &gst::Bin,
+    branch: &RecordingBranch,
+    options: &RecordingOptions,
+    start: gst::ClockTime,
+    clock: Arc<Mutex<PauseClock>>,
+) -> Result<Option<AttachedBranch>, IcedGStreamerError> {
+    if options.without_audio {
+        return Ok(None);
+    }
+    let codec = match options.audio_codec {
+        Some(codec) => codec,
+        None => {
+            let codec = AudioCodec::default_for(branch.container);
+            if !codec.is_available() {
+                log::warn!(
+                    "{} is not installed, the audio will not be recorded",
+                    codec.encoder_name()
+                );
+                return Ok(None);
+            }
+            codec
+        }
+    };
+
+    let bin = gst::Bin::new();
+    let queue = make_element("queue")?;
+    let audioconvert = make_element("audioconvert")?;
+    let audioresample = make_element("audioresample")?;
+    let encoder = make_element(codec.encoder_name())?;
+    let audio_app_sink = gst_app::AppSink::builder().sync(false).build();
+    // the recording should not hold the preroll of the playback
+    audio_app_sink.set_property("async", false);
+    let audio_app_sink_element: gst::Element = audio_app_sink.clone().into();
+    bin.add_many([
+        &queue,
+        &audioconvert,
+        &audioresample,
+        &encoder,
+        &audio_app_sink_element,
+    ])?;
+    gst::Element::link_many([
+        &queue,
+        &audioconvert,
+        &audioresample,
+        &encoder,
+        &audio_app_sink_element,
+    ])?;
+
+    let audio_app_src = gst_app::AppSrc::builder()
+        .format(gst::Format::Time)
+        .do_timestamp(false)
+        .build();
+    let audio_app_src_element: gst::Element = audio_app_src.clone().into();
+    video_record_bin.add(&audio_app_src_element)?;
+    branch.link_audio(&audio_app_src_element, &encoder, codec)?;
+
+    let app_src = audio_app_src.clone();
+    let bin_weak = bin.downgrade();
+    audio_app_sink.set_callbacks(
+        gst_app::AppSinkCallbacks::builder()
+            .new_sample(move |sink| {
+                let sample = sink.pull_sample().map_err(|_| gst::FlowError::Eos)?;
+                let caps = sample.caps().ok_or(gst::FlowError::NotNegotiated)?;
+                if app_src.caps().as_ref().map(|c| c.as_ref()) != Some(caps) {
+                    app_src.set_caps(Some(&caps.to_owned()));
+                }
+                let mut buffer = sample.buffer_owned().ok_or(gst::FlowError::Error)?;
+                // the segment of the appsrc begins with zero, use the running time since the
+                // recording is started
+                if let Some(segment) = sample
+                    .segment()
+                    .and_then(|segment| segment.downcast_ref::<gst::ClockTime>())
+                {
+                    let Some(paused_total) = buffer
+                        .pts()
+                        .and_then(|pts| segment.to_running_time(pts))
+                        .and_then(|time| clock.lock().unwrap().offset(time))
+                    else {
+                        return Ok(gst::FlowSuccess::Ok);
+                    };
+                    let running_time = |time: Option<gst::ClockTime>| {
+                        time.and_then(|time| segment.to_running_time(time))
+                            .and_then(|time| time.checked_sub(start + paused_total))
+                    };
+                    let pts = running_time(buffer.pts());
+                    if pts.is_none() {
+                        // before the beginning of the file
+                        return Ok(gst::FlowSuccess::Ok);
+                    }
+                    let dts = running_time(buffer.dts());
+                    let buffer = buffer.make_mut();
+                    buffer.set_pts(pts);
+                    buffer.set_dts(dts);
+                }
+                app_src.push_buffer(buffer)
+            })
+            .eos({
+                let app_src = audio_app_src.clone();
+                move |_| {
+                    let _ = app_src.end_of_stream();
+                    if let Some(bin) = bin_weak.upgrade() {
+                        remove_later(bin);
+                    }
+                }
+            })
+            .build(),
+    );
+
+    // the muxer waits for every pad, if the stream has no sound, the audio sink is never added
+    // to playbin, so end the audio pad once the video begins
+    let audio_bin_weak = audio_bin.downgrade();
+    branch.elements[0].static_pad("sink").unwrap().add_probe(
+        gst::PadProbeType::BUFFER,
+        move |_, _| {
+            if audio_bin_weak
+                .upgrade()
+                .is_none_or(|audio_bin| audio_bin.parent().is_none())
+            {
+                let _ = audio_app_src.end_of_stream();
+            }
+            gst::PadProbeReturn::Remove
+        },
+    );
+
+    Ok(Some(AttachedBranch::attach(
+        audio_bin,
+        bin,
+        &queue,
+        gst::ClockTime::ZERO,
+    )?))
+}
+
+/// The audio sink of playbin, the sound goes to the audio output through a tee, so the audio of a
+/// recording can be attached to it
+pub(crate) fn audio_sink_bin() -> Result<gst::Bin, IcedGStreamerError> {
+    let bin = gst::Bin::new();
+    let tee = gst::ElementFactory::make("tee").name(TEE_NAME).build()?;
+    let queue = make_element("queue")?;
+    let audio_output = make_element("autoaudiosink")?;
+    // it is replaced by set_audio_sink
+    audio_output.set_property("name", crate::audio_output::AUDIO_OUTPUT_NAME);
+    bin.add_many([&tee, &queue, &audio_output])?;
+    gst::Element::link_many([&tee, &queue, &audio_output])?;
+
+    let staticpad = tee.static_pad("sink").unwrap();
+    let sinkgost = gst::GhostPad::builder_with_target(&staticpad)?.build();
+    sinkgost.set_active(true)?;
+    bin.add_pad(&sinkgost)?;
+    Ok(bin)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn segment_pattern_before_the_extension() {
+        assert_eq!(
+            segment_pattern(Path::new("/tmp/record.mp4")),
+            PathBuf::from("/tmp/record-%05d.mp4")
+        );
+        assert_eq!(
+            segment_pattern(Path::new("record")),
+            PathBuf::from("record-%05d")
+        );
+        // a pattern of the user is kept
+        assert_eq!(
+            segment_pattern(Path::new("/tmp/part%03d.mkv")),
+            PathBuf::from("/tmp/part%03d.mkv")
+        );
+    }
+
+    #[test]
+    fn container_from_the_options_or_the_extension() {
+        let options = RecordingOptions::new();
+        assert_eq!(
+            options.container_for(Path::new("a.MKV")).unwrap(),
+            Container::Mkv
+        );
+        assert!(matches!(
+            options.container_for(Path::new("a")),
+            Err(IcedGStreamerError::NoExtension)
+        ));
+        assert!(matches!(
+            options.container_for(Path::new("a.gif")),
+            Err(IcedGStreamerError::UnsupportedExtension)
+        ));
+        let options = options.container(Container::Webm);
+        assert_eq!(
+            options.container_for(Path::new("a.mp4")).unwrap(),
+            Container::Webm
+        );
+    }
+
+    #[test]
+    fn codecs_and_muxers() {
+        assert_eq!(AudioCodec::default_for(Container::Webm), AudioCodec::Opus);
+        assert_eq!(AudioCodec::default_for(Container::Mp4), AudioCodec::Aac);
+        assert_eq!(VideoCodec::OpenH264.parser_name(), Some("h264parse"));
+        assert_eq!(VideoCodec::Vp9.parser_name(), None);
+
+        let options = RecordingOptions::new();
+        assert_eq!(options.muxer_name(Container::Mov), "qtmux");
+        let options = options.mp4_mode(Mp4Mode::Isofmp4(std::time::Duration::from_secs(1)));
+        assert_eq!(options.muxer_name(Container::Mp4), "isofmp4mux");
+        // the mode only applies to mp4 and mov
+        assert_eq!(options.muxer_name(Container::Mkv), "matroskamux");
+    }
+
+    #[test]
+    fn options_builder() {
+        let options = RecordingOptions::new()
+            .codec(VideoCodec::Vp8)
+            .quality(150)
+            .keyframe_interval(60)
+            .without_audio();
+        assert_eq!(options.codec, VideoCodec::Vp8);
+        assert_eq!(options.rate_control, Some(RateControl::Quality(100)));
+        assert_eq!(options.keyframe_interval, Some(60));
+        assert!(options.without_audio);
+        assert!(!options.is_segmented());
+
+        // the last rate control wins
+        let options = options.bitrate(4000).max_segment_size(1 << 20);
+        assert_eq!(options.rate_control, Some(RateControl::Bitrate(4000)));
+        assert!(options.is_segmented());
+    }
+
+    #[test]
+    fn pause_clock_offsets() {
+        let time = gst::ClockTime::from_seconds;
+        let mut clock = PauseClock::default();
+        assert_eq!(clock.offset(time(1)), Some(gst::ClockTime::ZERO));
+
+        clock.pause(time(2));
+        assert!(clock.is_paused());
+        assert_eq!(clock.offset(time(1)), Some(gst::ClockTime::ZERO));
+        assert_eq!(clock.offset(time(3)), None);
+        // pausing again does not move the start
+        clock.pause(time(3));
+        clock.resume(time(5));
+        assert!(!clock.is_paused());
+        // a buffer queued during the pause is still dropped after the resume
+        assert_eq!(clock.offset(time(4)), None);
+        assert_eq!(clock.offset(time(5)), Some(time(3)));
+
+        clock.pause(time(6));
+        clock.resume(time(7));
+        assert_eq!(clock.offset(time(5)), Some(time(3)));
+        assert_eq!(clock.offset(time(8)), Some(time(4)));
+    }
+}
@@ -5,11 +5,32 @@ struct VertexOutput {
 
 struct Uniforms {
     rect: vec4<f32>,
+    // subtracted from the yuv samples, from the range of the colorimetry
+    yuv_offset: vec4<f32>,
+    yuv_scale: vec4<f32>,
+    // the rows of the yuv to rgb matrix of the colorimetry
+    yuv2rgb: array<vec4<f32>, 3>,
+    format: u32,
+    transfer: u32,
 }
 
+const FORMAT_NV12: u32 = 0u;
+const FORMAT_I420: u32 = 1u;
+const FORMAT_RGBA: u32 = 2u;
+const FORMAT_P010: u32 = 3u;
+
+const TRANSFER_SDR: u32 = 0u;
+const TRANSFER_PQ: u32 = 1u;
+const TRANSFER_HLG: u32 = 2u;
+
+// the luminance of the SDR white, in nits
+const SDR_WHITE: f32 = 203.0;
+
+// Y for yuv formats, or the only plane of rgba formats
 @group(0) @binding(0)
 var tex_y: texture_2d<f32>;
 
+// UV for NV12 and P010, U for I420
 @group(0) @binding(1)
 var tex_uv: texture_2d<f32>;
 
@@ -19,6 +40,10 @@ var s: sampler;
 @group(0) @binding(3)
 var<uniform> uniforms: Uniforms;
 
+// V for I420
+@group(0) @binding(4)
+var tex_v: texture_2d<f32>;
+
 @vertex
 fn vs_main(@builtin(vertex_index) in_vertex_index: u32) -> VertexOutput {
     var quad = array<vec4<f32>, 6>(
@@ -36,21 +61,90 @@ fn vs_main(@builtin(vertex_index) in_vertex_index: u32) -> VertexOutput {
     return out;
 }
 
+// 16 bits samples are uploaded as two 8 bits channels, low byte first
+fn combine_16(low: f32, high: f32) -> f32 {
+    return (low + high * 256.0) * 255.0 / 65535.0;
+}
+
+// SMPTE ST 2084 to nits
+fn pq_to_nits(value: vec3<f32>) -> vec3<f32> {
+    let m1 = 0.1593017578125;
+    let m2 = 78.84375;
+    let c1 = 0.8359375;
+    let c2 = 18.8515625;
+    let c3 = 18.6875;
+    let p = pow(max(value, vec3<f32>(0.0)), vec3<f32>(1.0 / m2));
+    let linear = pow(max(p - c1, vec3<f32>(0.0)) / (c2 - c3 * p), vec3<f32>(1.0 / m1));
+    return linear * 10000.0;
+}
+
+// ARIB STD-B67 to nits, with the OOTF of a 1000 nits display
+fn hlg_to_nits(value: vec3<f32>) -> vec3<f32> {
+    let a = 0.17883277;
+    let b = 0.28466892;
+    let c = 0.55991073;
+    let low = value * value / 3.0;
+    let high = (exp((value - c) / a) + b) / 12.0;
+    let scene = select(high, low, value <= vec3<f32>(0.5));
+    return pow(max(scene, vec3<f32>(0.0)), vec3<f32>(1.2)) * 1000.0;
+}
+
+// HDR video in BT.2020 is tone mapped to SDR in BT.709
+fn tone_map(nits: vec3<f32>) -> vec3<f32> {
+    let bt2020_to_bt709 = mat3x3<f32>(
+        1.6605, -0.5876, -0.0728,
+        -0.1246, 1.1329, -0.0083,
+        -0.0182, -0.1006, 1.1187,
+    );
+    let linear = max((nits / SDR_WHITE) * bt2020_to_bt709, vec3<f32>(0.0));
+    let white = 10000.0 / SDR_WHITE;
+    let mapped = linear * (1.0 + linear / (white * white)) / (1.0 + linear);
+    return pow(mapped, vec3<f32>(1.0 / 2.2));
+}
+
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    // BT.709 precomputed coefficients
-    let yuv2rgb = mat3x3<f32>(
-        1, 0, 1.5748,
-        1, -0.1873, -0.4681,
-        1, 1.8556, 0,
-    );
+    let plane0 = textureSample(tex_y, s, in.uv);
+    let plane1 = textureSample(tex_uv, s, in.uv);
+    let plane2 = textureSample(tex_v, s, in.uv);
+
+    if uniforms.format == FORMAT_RGBA {
+        return vec4<f32>(plane0.rgb, 1.0);
+    }
 
-    var yuv = vec3<f32>(0.0);
-    yuv.x = (textureSample(tex_y, s, in.uv).r - 0.0625) / 0.8588;
-    yuv.y = (textureSample(tex_uv, s, in.uv).r - 0.5) / 0.8784;
-    yuv.z = (textureSample(tex_uv, s, in.uv).g - 0.5) / 0.8784;
+    var raw = vec3<f32>(0.0);
+    switch uniforms.format {
+        case FORMAT_I420: {
+            raw = vec3<f32>(plane0.r, plane1.r, plane2.r);
+        }
+        case FORMAT_P010: {
+            raw = vec3<f32>(
+                combine_16(plane0.r, plane0.g),
+                combine_16(plane1.r, plane1.g),
+                combine_16(plane1.b, plane1.a),
+            );
+        }
+        default: {
+            raw = vec3<f32>(plane0.r, plane1.r, plane1.g);
+        }
+    }
+
+    let yuv = (raw - uniforms.yuv_offset.xyz) / uniforms.yuv_scale.xyz;
+    var rgb = vec3<f32>(
+        dot(yuv, uniforms.yuv2rgb[0].xyz),
+        dot(yuv, uniforms.yuv2rgb[1].xyz),
+        dot(yuv, uniforms.yuv2rgb[2].xyz),
+    );
 
-    var rgb = clamp(yuv * yuv2rgb, vec3<f32>(0), vec3<f32>(1));
+    switch uniforms.transfer {
+        case TRANSFER_PQ: {
+            rgb = tone_map(pq_to_nits(clamp(rgb, vec3<f32>(0), vec3<f32>(1))));
+        }
+        case TRANSFER_HLG: {
+            rgb = tone_map(hlg_to_nits(clamp(rgb, vec3<f32>(0), vec3<f32>(1))));
+        }
+        default: {}
+    }
 
-    return vec4<f32>(rgb, 1.0);
+    return vec4<f32>(clamp(rgb, vec3<f32>(0), vec3<f32>(1)), 1.0);
 }
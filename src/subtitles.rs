@@ -0,0 +1,235 @@
+use gst::prelude::*;
+use gstreamer as gst;
+use gstreamer_app as gst_app;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// A subtitle stream of the media, the fields are None if the tags do not have them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubtitleTrack {
+    /// the index for [crate::GVideoUrl::select_subtitle_track]
+    pub index: usize,
+    pub language: Option<String>,
+    pub codec: Option<String>,
+    pub title: Option<String>,
+}
+
+impl SubtitleTrack {
+    pub(crate) fn new(index: usize, tags: Option<&gst::TagListRef>) -> Self {
+        Self {
+            index,
+            language: tags
+                .and_then(|tags| {
+                    tags.get::<gst::tags::LanguageName>()
+                        .or_else(|| tags.get::<gst::tags::LanguageCode>())
+                })
+                .map(|language| language.get().to_owned()),
+            codec: tags
+                .and_then(|tags| {
+                    tags.get::<gst::tags::SubtitleCodec>()
+                        .or_else(|| tags.get::<gst::tags::Codec>())
+                })
+                .map(|codec| codec.get().to_owned()),
+            title: tags
+                .and_then(|tags| tags.get::<gst::tags::Title>())
+                .map(|title| title.get().to_owned()),
+        }
+    }
+}
+
+/// A text of the subtitles, shown from start until end in the time of the media
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubtitleCue {
+    /// the plain text, the markup is removed
+    pub text: String,
+    pub start: Duration,
+    /// None if it is shown until the next cue
+    pub end: Option<Duration>,
+}
+
+impl SubtitleCue {
+    fn from_sample(sample: &gst::SampleRef) -> Option<Self> {
+        let buffer = sample.buffer()?;
+        let segment = sample.segment()?.downcast_ref::<gst::ClockTime>()?;
+        let pts = buffer.pts()?;
+        let start = segment.to_stream_time(pts)?;
+        let end = buffer
+            .duration()
+            .and_then(|duration| segment.to_stream_time(pts + duration));
+        let map = buffer.map_readable().ok()?;
+        let text = std::str::from_utf8(&map).ok()?.trim_end_matches('\0');
+        let markup = sample
+            .caps()
+            .and_then(|caps| caps.structure(0))
+            .and_then(|structure| structure.get::<&str>("format").ok())
+            == Some("pango-markup");
+        Some(Self {
+            text: if markup {
+                strip_markup(text)
+            } else {
+                text.to_owned()
+            },
+            start: Duration::from_nanos(start.nseconds()),
+            end: end.map(|end| Duration::from_nanos(end.nseconds())),
+        })
+    }
+
+    /// if the cue is shown at the position of the media
+    pub fn is_shown_at(&self, position: Duration) -> bool {
+        self.end.is_none_or(|end| position < end)
+    }
+}
+
+/// pango markup without the tags and with the entities replaced
+fn strip_markup(markup: &str) -> String {
+    let mut text = String::with_capacity(markup.len());
+    let mut in_tag = false;
+    for c in markup.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            c if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Parse the cues of a SRT or a WebVTT file, the cues which can not be parsed are skipped.
+/// The markup like `<i>` is removed
+pub fn parse_subtitles(text: &str) -> Vec<SubtitleCue> {
+    let text = text.trim_start_matches('\u{feff}').replace("\r\n", "\n");
+    let mut cues: Vec<SubtitleCue> = text
+        .split("\n\n")
+        .filter_map(|block| {
+            // the lines before the timing are the identifier of the cue
+            let mut lines = block.lines().skip_while(|line| !line.contains("-->"));
+            let (start, end) = lines.next()?.split_once("-->")?;
+            let end = end.split_whitespace().next()?;
+            let text = lines.collect::<Vec<_>>().join("\n");
+            Some(SubtitleCue {
+                text: strip_markup(&text),
+                start: parse_timestamp(start)?,
+                end: Some(parse_timestamp(end)?),
+            })
+        })
+        .collect();
+    cues.sort_by_key(|cue| cue.start);
+    cues
+}
+
+/// `hh:mm:ss,mmm` of SRT or `[hh:]mm:ss.mmm` of WebVTT
+fn parse_timestamp(timestamp: &str) -> Option<Duration> {
+    let (clock, millis) = timestamp.trim().split_once([',', '.'])?;
+    let mut seconds = 0;
+    for part in clock.split(':') {
+        seconds = seconds * 60 + part.parse::<u64>().ok()?;
+    }
+    Some(Duration::from_secs(seconds) + Duration::from_millis(millis.parse().ok()?))
+}
+
+/// The cues shown at the position, overlapping cues are joined into one
+pub(crate) fn cue_at(cues: &[SubtitleCue], position: Duration) -> Option<SubtitleCue> {
+    let shown: Vec<&SubtitleCue> = cues
+        .iter()
+        .take_while(|cue| cue.start <= position)
+        .filter(|cue| cue.is_shown_at(position))
+        .collect();
+    Some(SubtitleCue {
+        text: shown
+            .iter()
+            .map(|cue| cue.text.as_str())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        start: shown.iter().map(|cue| cue.start).max()?,
+        end: shown.iter().filter_map(|cue| cue.end).min(),
+    })
+}
+
+/// The text-sink of playbin, the cues are stored for the [crate::VideoPlayer] to draw them
+pub(crate) fn text_sink(cue: &Arc<Mutex<Option<SubtitleCue>>>) -> gst::Element {
+    let text_sink = gst_app::AppSink::builder()
+        .name("text_sink")
+        .caps(
+            &gst::Caps::builder("text/x-raw")
+                .field("format", gst::List::new(["utf8", "pango-markup"]))
+                .build(),
+        )
+        .build();
+
+    let cue_i = cue.clone();
+    text_sink.set_callbacks(
+        gst_app::AppSinkCallbacks::builder()
+            .new_sample(move |sink| {
+                let sample = sink.pull_sample().map_err(|_| gst::FlowError::Eos)?;
+                if let Some(cue) = SubtitleCue::from_sample(&sample) {
+                    *cue_i.lock().map_err(|_| gst::FlowError::Eos)? = Some(cue);
+                }
+                Ok(gst::FlowSuccess::Ok)
+            })
+            .build(),
+    );
+
+    // the cue before a seek is not shown after it
+    let cue_f = cue.clone();
+    text_sink.static_pad("sink").unwrap().add_probe(
+        gst::PadProbeType::EVENT_FLUSH,
+        move |_, info| {
+            if let Some(event) = info.event()
+                && event.type_() == gst::EventType::FlushStop
+            {
+                *cue_f.lock().unwrap() = None;
+            }
+            gst::PadProbeReturn::Ok
+        },
+    );
+
+    text_sink.upcast()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_srt() {
+        let cues = parse_subtitles(
+            "1\r\n00:00:01,000 --> 00:00:02,500\r\n<i>Hello</i> &amp; welcome\r\n\r\n\
+             2\r\n01:00:03,000 --> 01:00:04,000\r\nsecond\r\nline\r\n",
+        );
+        assert_eq!(
+            cues,
+            vec![
+                SubtitleCue {
+                    text: "Hello & welcome".to_owned(),
+                    start: Duration::from_millis(1000),
+                    end: Some(Duration::from_millis(2500)),
+                },
+                SubtitleCue {
+                    text: "second\nline".to_owned(),
+                    start: Duration::from_secs(3603),
+                    end: Some(Duration::from_secs(3604)),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_vtt() {
+        let cues = parse_subtitles(
+            "WEBVTT\n\nNOTE a comment\n\nintro\n00:01.000 --> 00:02.000 align:start\n\
+             <v Anna>Hi\n\n00:01.500 --> 00:03.000\nthere\n",
+        );
+        assert_eq!(cues.len(), 2);
+        assert_eq!(cues[0].text, "Hi");
+        assert_eq!(cues[0].start, Duration::from_secs(1));
+        let cue = cue_at(&cues, Duration::from_millis(1700)).unwrap();
+        assert_eq!(cue.text, "Hi\nthere");
+        assert_eq!(cue.end, Some(Duration::from_secs(2)));
+        assert_eq!(cue_at(&cues, Duration::from_secs(5)), None);
+    }
+}
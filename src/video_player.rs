@@ -1,16 +1,17 @@
 use std::sync::atomic::Ordering;
 
 use crate::GVideo;
+use crate::GVideoEvent;
+use crate::RecordingState;
 use crate::StreamType;
 use crate::pipeline::VideoPrimitive;
 use gst::State;
 use gstreamer as gst;
-use gstreamer::GenericFormattedValue;
 use gstreamer::glib;
 use gstreamer::prelude::*;
 use iced_core::{
     Background, Border, Color, ContentFit, Element, Point, Rectangle, Shadow, Size, Theme, Vector,
-    Widget, border, layout, svg,
+    Widget, border, layout, svg, text,
 };
 use iced_wgpu::primitive::Renderer as PrimitiveRenderer;
 use std::time::{Duration, Instant};
@@ -37,6 +38,15 @@ pub struct Style {
 
     /// The background [`Color`] of the video.
     pub video_background: Color,
+
+    /// The [`Color`] of the subtitles.
+    pub subtitle_color: Color,
+    /// The background [`Color`] behind the subtitles.
+    pub subtitle_background: Color,
+    /// The font size of the subtitles, it follows the height of the video if None.
+    pub subtitle_size: Option<f32>,
+    /// Where the bottom of the subtitles is, from 0 at the top of the video to 1 at its bottom.
+    pub subtitle_position: f32,
 }
 
 impl Style {
@@ -62,6 +72,10 @@ impl Default for Style {
             background: None,
             icon_color: Color::WHITE,
             video_background: Color::BLACK,
+            subtitle_color: Color::WHITE,
+            subtitle_background: Color::from_rgba(0., 0., 0., 0.6),
+            subtitle_size: None,
+            subtitle_position: 0.95,
             border: Border::default(),
             shadow: Shadow::default(),
             snap: false,
@@ -131,6 +145,9 @@ where
     on_duration_changed: Option<Box<dyn Fn(Duration) -> Message + 'a>>,
     on_position_changed: Option<Box<dyn Fn(Duration) -> Message + 'a>>,
     on_state_changed: Option<Box<dyn Fn(State) -> Message + 'a>>,
+    on_event: Option<Box<dyn Fn(GVideoEvent) -> Message + 'a>>,
+    frame_step_keys: bool,
+    scroll_volume: bool,
     status_bar: Option<Element<'a, Message, Theme, Renderer>>,
     menu: Option<Element<'a, Message, Theme, Renderer>>,
     status_bar_delay: u64,
@@ -157,6 +174,9 @@ where
             on_duration_changed: None,
             on_position_changed: None,
             on_state_changed: None,
+            on_event: None,
+            frame_step_keys: false,
+            scroll_volume: false,
             menu: None,
             status_bar: None,
             status_bar_delay: 2,
@@ -224,6 +244,25 @@ where
         }
     }
 
+    /// The events which only the [VideoPlayer] reports, the resolution, the position, the
+    /// subtitle and the recording state, see [GVideoEvent]. The messages of the bus are received
+    /// with [crate::GVideo::subscription], so they are not sent twice
+    pub fn on_event<F>(self, on_event: F) -> Self
+    where
+        F: 'a + Fn(GVideoEvent) -> Message,
+    {
+        VideoPlayer {
+            on_event: Some(Box::new(on_event)),
+            ..self
+        }
+    }
+
+    fn publish_event(&self, shell: &mut iced_core::Shell<'_, Message>, event: GVideoEvent) {
+        if let Some(on_event) = &self.on_event {
+            shell.publish(on_event(event));
+        }
+    }
+
     /// The position changed during playing
     pub fn on_position_changed<F>(self, on_position_changed: F) -> Self
     where
@@ -251,6 +290,22 @@ where
         }
     }
 
+    /// Step one frame backward with `,` and forward with `.`, only for [GVideo::UrlPlayer]
+    pub fn frame_step_keys(self, frame_step_keys: bool) -> Self {
+        VideoPlayer {
+            frame_step_keys,
+            ..self
+        }
+    }
+
+    /// Change the volume with the scroll wheel, only for [GVideo::UrlPlayer]
+    pub fn scroll_volume(self, scroll_volume: bool) -> Self {
+        VideoPlayer {
+            scroll_volume,
+            ..self
+        }
+    }
+
     /// Set the height of status bar
     pub fn status_bar_height(self, status_bar_height: f32) -> Self {
         VideoPlayer {
@@ -345,6 +400,71 @@ where
     }
 }
 
+impl<Message, Theme, Renderer> VideoPlayer<'_, Message, Theme, Renderer>
+where
+    Renderer: text::Renderer<Font = iced_core::Font>,
+    Theme: Catalog,
+{
+    /// Draw the subtitle where the [Style] places it, by default at the bottom of the video
+    fn draw_subtitle(
+        &self,
+        renderer: &mut Renderer,
+        theme: &Theme,
+        drawing_bounds: Rectangle,
+        text: &str,
+    ) {
+        let style = theme.style(&self.class);
+        let size = style
+            .subtitle_size
+            .unwrap_or((drawing_bounds.height / SUBTITLE_SCALE).max(MIN_SUBTITLE_SIZE));
+        let padding = size / 4.;
+        let paragraph = text::Text {
+            content: text.to_owned(),
+            bounds: Size::new(drawing_bounds.width * 0.9, drawing_bounds.height),
+            size: size.into(),
+            line_height: text::LineHeight::default(),
+            font: renderer.default_font(),
+            align_x: text::Alignment::Center,
+            align_y: iced_core::alignment::Vertical::Bottom,
+            shaping: text::Shaping::Advanced,
+            wrapping: text::Wrapping::Word,
+        };
+        let text_size = text::Paragraph::min_bounds(
+            &<Renderer::Paragraph as text::Paragraph>::with_text(paragraph.as_ref()),
+        );
+        let bottom = drawing_bounds.y
+            + (drawing_bounds.height * style.subtitle_position.clamp(0., 1.))
+                .max(text_size.height + padding);
+        let background = Rectangle::new(
+            Point::new(
+                drawing_bounds.center_x() - text_size.width / 2. - padding,
+                bottom - text_size.height - padding,
+            ),
+            Size::new(
+                text_size.width + padding * 2.,
+                text_size.height + padding * 2.,
+            ),
+        );
+
+        renderer.with_layer(drawing_bounds, |renderer| {
+            renderer.fill_quad(
+                iced_core::renderer::Quad {
+                    bounds: background,
+                    border: border::rounded(padding),
+                    ..Default::default()
+                },
+                style.subtitle_background,
+            );
+            renderer.fill_text(
+                paragraph,
+                Point::new(drawing_bounds.center_x(), bottom),
+                style.subtitle_color,
+                drawing_bounds,
+            );
+        });
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 enum Direction {
     Playing,
@@ -362,10 +482,76 @@ struct VideoState {
     limits: iced_core::layout::Limits,
     direction: Direction,
     opacity: f32,
+    recording_state: RecordingState,
+    /// the subtitle shown at the last update
+    subtitle: Option<crate::SubtitleCue>,
 }
 
 const PLAY_ICON_SCALE: f32 = 6.0;
 
+/// how much a line of the scroll wheel changes the cubic volume
+const VOLUME_STEP: f64 = 0.05;
+/// the scroll of a touchpad in pixels which counts as a line
+const PIXELS_PER_LINE: f32 = 20.0;
+
+/// the subtitles are a twentieth of the height of the video
+const SUBTITLE_SCALE: f32 = 20.0;
+const MIN_SUBTITLE_SIZE: f32 = 14.0;
+
+/// A seek which plays from the position, or plays backward from it when the rate is negative.
+/// While looping, it is a segment seek inside the loop range, which posts SegmentDone instead of
+/// EOS at its end
+pub(crate) fn seek_event(
+    state: &crate::State,
+    flags: gst::SeekFlags,
+    position: gst::ClockTime,
+) -> gst::Event {
+    let (flags, start, stop, position) = match state.loop_bounds() {
+        Some((start, stop)) => {
+            let position = position.max(start);
+            let position = stop.map_or(position, |stop| position.min(stop));
+            (flags | gst::SeekFlags::SEGMENT, start, stop, position)
+        }
+        None => (flags, gst::ClockTime::ZERO, None, position),
+    };
+    if state.rate < 0. {
+        gst::event::Seek::new(
+            state.rate,
+            flags,
+            gst::SeekType::Set,
+            start,
+            gst::SeekType::Set,
+            position,
+        )
+    } else {
+        gst::event::Seek::new(
+            state.rate,
+            flags,
+            gst::SeekType::Set,
+            Some(position),
+            if stop.is_some() {
+                gst::SeekType::Set
+            } else {
+                gst::SeekType::None
+            },
+            stop,
+        )
+    }
+}
+
+/// where the next loop starts, the end of the loop range when playing backward
+pub(crate) fn loop_start(state: &crate::State) -> gst::ClockTime {
+    let (start, stop) = state.loop_bounds().unwrap_or((gst::ClockTime::ZERO, None));
+    if state.rate < 0. {
+        stop.unwrap_or(gst::ClockTime::from_nseconds(state.duration.as_nanos() as _))
+    } else {
+        start
+    }
+}
+
+/// how often the duration and the position are queried
+const QUERY_INTERVAL: Duration = Duration::from_millis(100);
+
 impl VideoState {
     #[inline]
     fn skip_opacity_change(&self) -> bool {
@@ -396,7 +582,7 @@ impl<Message, Theme, Renderer> Widget<Message, Theme, Renderer>
     for VideoPlayer<'_, Message, Theme, Renderer>
 where
     Message: Clone,
-    Renderer: PrimitiveRenderer + svg::Renderer,
+    Renderer: PrimitiveRenderer + svg::Renderer + text::Renderer<Font = iced_core::Font>,
     Theme: Catalog,
 {
     fn size(&self) -> iced_core::Size<iced_core::Length> {
@@ -433,6 +619,8 @@ where
             icon_instant: Instant::now().checked_add(Duration::from_secs(1)).unwrap(),
             direction,
             opacity,
+            recording_state: self.video.recording_state(),
+            subtitle: None,
         })
     }
 
@@ -624,6 +812,17 @@ where
         } else {
             render(renderer);
         }
+
+        if let GVideo::UrlPlayer(player) = self.video
+            && let Some(subtitle) = player.subtitle()
+        {
+            self.draw_subtitle(
+                renderer,
+                theme,
+                drawing_bounds.intersection(&bounds).unwrap_or(bounds),
+                &subtitle.text,
+            );
+        }
     }
     fn update(
         &mut self,
@@ -680,6 +879,35 @@ where
                 video_state.status_bar_shown = true;
                 return;
             }
+            iced_core::Event::Keyboard(iced_core::keyboard::Event::KeyPressed {
+                key: iced_core::keyboard::Key::Character(key),
+                ..
+            }) if self.frame_step_keys && matches!(key.as_str(), "," | ".") => {
+                if let GVideo::UrlPlayer(player) = self.video {
+                    if key.as_str() == "." {
+                        player.step_forward(1);
+                    } else {
+                        player.step_backward(1);
+                    }
+                    video_state.direction = Direction::Playing;
+                }
+                shell.request_redraw();
+                return;
+            }
+            iced_core::Event::Mouse(iced_core::mouse::Event::WheelScrolled { delta })
+                if self.scroll_volume && cursor.is_over(layout.bounds()) =>
+            {
+                if let GVideo::UrlPlayer(player) = self.video {
+                    let lines = match delta {
+                        iced_core::mouse::ScrollDelta::Lines { y, .. } => *y,
+                        iced_core::mouse::ScrollDelta::Pixels { y, .. } => y / PIXELS_PER_LINE,
+                    };
+                    player.set_volume_cubic(player.volume_cubic() + lines as f64 * VOLUME_STEP);
+                }
+                shell.capture_event();
+                shell.request_redraw();
+                return;
+            }
             iced_core::Event::Mouse(event) => {
                 use iced_core::mouse::Button;
                 video_state.instant = Instant::now()
@@ -746,6 +974,9 @@ where
             let (width, height) = data.size();
             let image_size = Size::new(width as f32, height as f32);
 
+            if video_state.size != Some(image_size) {
+                self.publish_event(shell, GVideoEvent::ResolutionChanged { width, height });
+            }
             video_state.size = Some(image_size);
         }
 
@@ -757,76 +988,162 @@ where
         if self.video.stream_type() == StreamType::UrlPlayer && alive {
             for event in self.video.pending_events() {
                 match event {
-                    crate::GsEvent::Jump(position) => {
-                        let position: GenericFormattedValue = position.into();
-                        let _ = self
-                            .video
-                            .source()
-                            .unwrap()
-                            .seek_simple(gst::SeekFlags::FLUSH, position);
+                    crate::GsEvent::Jump(position, mode) => {
+                        let source = self.video.source().unwrap();
+                        let framerate = match self.video {
+                            GVideo::UrlPlayer(player) => player.framerate(),
+                            _ => None,
+                        };
+                        // playbin does not take the frames in the Default format
+                        let Some(time) = position.to_time(framerate) else {
+                            crate::events::post_seek_failed(source, position);
+                            continue;
+                        };
+                        let seek = seek_event(&state, mode.flags(), time);
+                        // the AsyncDone message with this seqnum ends the seek
+                        let seqnum = seek.seqnum();
+                        if source.send_event(seek) {
+                            state.seek_seqnum = Some(seqnum);
+                        } else {
+                            crate::events::post_seek_failed(source, position);
+                        }
+                    }
+                    crate::GsEvent::Rate(rate) => {
+                        let source = self.video.source().unwrap();
+                        let previous = std::mem::replace(&mut state.rate, rate);
+                        // in the same direction, the rate can be changed without flushing
+                        if previous.signum() == rate.signum()
+                            && source.send_event(gst::event::Seek::new(
+                                rate,
+                                gst::SeekFlags::INSTANT_RATE_CHANGE,
+                                gst::SeekType::None,
+                                gst::ClockTime::NONE,
+                                gst::SeekType::None,
+                                gst::ClockTime::NONE,
+                            ))
+                        {
+                            continue;
+                        }
+                        let position = source
+                            .query_position::<gst::ClockTime>()
+                            .unwrap_or(gst::ClockTime::ZERO);
+                        let seek = seek_event(
+                            &state,
+                            gst::SeekFlags::FLUSH | gst::SeekFlags::ACCURATE,
+                            position,
+                        );
+                        state.seek_seqnum = Some(seek.seqnum());
+                        source.send_event(seek);
+                    }
+                    crate::GsEvent::Step(frames) => {
+                        let source = self.video.source().unwrap();
+                        // the frames are stepped in the direction of the rate
+                        if (frames < 0) != (state.rate < 0.) {
+                            state.rate = -state.rate;
+                            let position = source
+                                .query_position::<gst::ClockTime>()
+                                .unwrap_or(gst::ClockTime::ZERO);
+                            let seek = seek_event(
+                                &state,
+                                gst::SeekFlags::FLUSH | gst::SeekFlags::ACCURATE,
+                                position,
+                            );
+                            state.seek_seqnum = Some(seek.seqnum());
+                            source.send_event(seek);
+                        }
+                        // only the video is stepped, the audio sink would drift away from it
+                        let step = gst::event::Step::new(
+                            gst::format::Buffers::from_u64(frames.unsigned_abs()),
+                            1.,
+                            true,
+                            false,
+                        );
+                        if let Some(video_sink) =
+                            source.property::<Option<gst::Element>>("video-sink")
+                            && video_sink.send_event(step)
+                        {
+                            state.step_pending = true;
+                        }
+                    }
+                    crate::GsEvent::Loop => {
+                        // the segment flag of the current playback is replaced
+                        let source = self.video.source().unwrap();
+                        let position = source
+                            .query_position::<gst::ClockTime>()
+                            .unwrap_or(gst::ClockTime::ZERO);
+                        let seek = seek_event(
+                            &state,
+                            gst::SeekFlags::FLUSH | gst::SeekFlags::ACCURATE,
+                            position,
+                        );
+                        state.seek_seqnum = Some(seek.seqnum());
+                        source.send_event(seek);
                     }
                 }
             }
-            if state.get_duration_attempt && self.video.play_state() == gst::State::Playing {
-                loop {
-                    self.video
-                        .source()
-                        .unwrap()
-                        .state(gst::ClockTime::from_seconds(1))
-                        .0
-                        .unwrap();
-
-                    if let Some(time) = self
-                        .video
-                        .source()
-                        .unwrap()
-                        .query_duration::<gst::ClockTime>()
-                    {
-                        state.duration = std::time::Duration::from_nanos(time.nseconds());
+            // the queries do not wait for the pipeline, if they fail, they are tried again later
+            if state
+                .last_query
+                .is_none_or(|last_query| last_query.elapsed() >= QUERY_INTERVAL)
+                && matches!(
+                    self.video.play_state(),
+                    gst::State::Playing | gst::State::Paused
+                )
+            {
+                state.last_query = Some(Instant::now());
+                let source = self.video.source().unwrap();
+                if state.get_duration_attempt
+                    && let Some(time) = source.query_duration::<gst::ClockTime>()
+                {
+                    state.get_duration_attempt = false;
+                    let duration = Duration::from_nanos(time.nseconds());
+                    if duration != state.duration {
+                        state.duration = duration;
                         if let Some(on_duration_changed) = &self.on_duration_changed {
-                            shell.publish(on_duration_changed(state.duration));
+                            shell.publish(on_duration_changed(duration));
                         }
-                        break;
                     }
                 }
-                state.get_duration_attempt = false;
-            }
-            if state.duration.as_nanos() != 0 {
-                loop {
-                    if let Some(time) = self
-                        .video
-                        .source()
-                        .unwrap()
-                        .query_position::<gst::ClockTime>()
-                    {
-                        state.position = std::time::Duration::from_nanos(time.nseconds());
+                if !state.duration.is_zero()
+                    && let Some(time) = source.query_position::<gst::ClockTime>()
+                {
+                    let position = Duration::from_nanos(time.nseconds());
+                    if position != state.position {
+                        state.position = position;
                         if let Some(on_position_changed) = &self.on_position_changed {
-                            shell.publish(on_position_changed(state.position));
+                            shell.publish(on_position_changed(position));
                         }
-                        break;
+                        self.publish_event(shell, GVideoEvent::PositionChanged(position));
                     }
-                    self.video
-                        .source()
-                        .unwrap()
-                        .state(gst::ClockTime::from_seconds(5))
-                        .0
-                        .unwrap();
                 }
             }
             state.volume = self.video.source().unwrap().property("volume");
         }
+        // a paused video still shows the frame and the position of a step or a seek
         if matches!(
             self.video.play_state(),
             gst::State::Playing | gst::State::Ready
-        ) {
+        ) || state.step_pending
+            || self.video.upload_frame().unwrap().load(Ordering::Relaxed)
+        {
             shell.request_redraw();
         }
 
-        while let Some(msg) = self.video.bus().unwrap().pop_filtered(&[
-            gst::MessageType::Error,
-            gst::MessageType::Eos,
-            gst::MessageType::StateChanged,
-        ]) {
+        if let GVideo::UrlPlayer(player) = self.video {
+            let subtitle = player.subtitle_with_state(&state);
+            if subtitle != video_state.subtitle {
+                video_state.subtitle = subtitle.clone();
+                self.publish_event(shell, GVideoEvent::SubtitleChanged(subtitle));
+            }
+        }
+
+        let recording_state = self.video.recording_state();
+        if recording_state != video_state.recording_state {
+            video_state.recording_state = recording_state;
+            self.publish_event(shell, GVideoEvent::RecordingStateChanged(recording_state));
+        }
+
+        while let Some(msg) = self.video.bus().unwrap().pop() {
             match msg.view() {
                 gst::MessageView::Error(err) => {
                     log::error!("bus returned an error: {err}");
@@ -834,6 +1151,8 @@ where
                         shell.publish(on_error(&err.error()))
                     };
                 }
+                // the video starts the next loop by itself, see GVideoUrl::set_looping
+                gst::MessageView::Eos(_) | gst::MessageView::SegmentDone(_) if state.looping => {}
                 gst::MessageView::Eos(_eos) => {
                     self.video
                         .source()
@@ -862,6 +1181,26 @@ where
                         video_state.icon_instant = Instant::now();
                     }
                 }
+                gst::MessageView::DurationChanged(_)
+                | gst::MessageView::AsyncDone(_)
+                | gst::MessageView::StreamStart(_) => {
+                    // query it again in the next update
+                    state.get_duration_attempt = true;
+                }
+                gst::MessageView::ClockLost(_)
+                    if self.video.play_state() == gst::State::Playing =>
+                {
+                    // a new clock is selected when it plays again, the removed audio output of
+                    // set_audio_sink provided the old one
+                    let source = self.video.source().unwrap();
+                    let _ = source.set_state(gst::State::Paused);
+                    let _ = source.set_state(gst::State::Playing);
+                }
+                gst::MessageView::StepDone(_) => {
+                    // show the new position without waiting for the next query
+                    state.last_query = None;
+                    state.step_pending = false;
+                }
                 _ => {}
             }
         }
@@ -1055,7 +1394,7 @@ impl<'a, Message, Theme, Renderer> From<VideoPlayer<'a, Message, Theme, Renderer
 where
     Message: 'a + Clone,
     Theme: 'a,
-    Renderer: 'a + PrimitiveRenderer + svg::Renderer,
+    Renderer: 'a + PrimitiveRenderer + svg::Renderer + text::Renderer<Font = iced_core::Font>,
     Theme: Catalog,
 {
     fn from(video_player: VideoPlayer<'a, Message, Theme, Renderer>) -> Self {